@@ -0,0 +1,112 @@
+use std::ops::{Bound, RangeBounds};
+
+use cgmath::Point3;
+
+use crate::{Float, Ray};
+
+/// Axis-aligned bounding box
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<Float>,
+    pub max: Point3<Float>,
+}
+
+impl Aabb {
+    pub fn new(min: Point3<Float>, max: Point3<Float>) -> Self {
+        Self { min, max }
+    }
+
+    /// Slab test. Rays that only graze an edge or a corner count as hits.
+    pub fn hit<R: RangeBounds<Float>>(&self, ray: &Ray, t_range: R) -> bool {
+        let mut t_min = match t_range.start_bound() {
+            Bound::Included(t) | Bound::Excluded(t) => *t,
+            Bound::Unbounded => Float::NEG_INFINITY,
+        };
+        let mut t_max = match t_range.end_bound() {
+            Bound::Included(t) | Bound::Excluded(t) => *t,
+            Bound::Unbounded => Float::INFINITY,
+        };
+        for axis in 0..3 {
+            let inverse_direction = 1.0 / ray.direction[axis];
+            let t0 = (self.min[axis] - ray.origin[axis]) * inverse_direction;
+            let t1 = (self.max[axis] - ray.origin[axis]) * inverse_direction;
+            let (t0, t1) = if inverse_direction < 0.0 {
+                (t1, t0)
+            } else {
+                (t0, t1)
+            };
+            // Float::max/min ignore the NaN produced by a ray lying in a slab plane
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max < t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+pub fn surrounding_box(a: &Aabb, b: &Aabb) -> Aabb {
+    Aabb::new(
+        Point3::new(
+            a.min.x.min(b.min.x),
+            a.min.y.min(b.min.y),
+            a.min.z.min(b.min.z),
+        ),
+        Point3::new(
+            a.max.x.max(b.max.x),
+            a.max.y.max(b.max.y),
+            a.max.z.max(b.max.z),
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use super::*;
+
+    fn unit_box() -> Aabb {
+        Aabb::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn rays_grazing_a_corner_hit_from_either_side() {
+        let aabb = unit_box();
+        let direction = Vector3::new(1.0, -1.0, -1.0);
+        let toward = Ray::new(Point3::new(-1.0, 1.0, 1.0), direction);
+        let away = Ray::new(Point3::new(1.0, -1.0, -1.0), -direction);
+        assert!(aabb.hit(&toward, 0.0..Float::INFINITY));
+        assert!(aabb.hit(&away, 0.0..Float::INFINITY));
+
+        let beside = Ray::new(Point3::new(-1.001, 1.0, 1.0), direction);
+        assert!(!aabb.hit(&beside, 0.0..Float::INFINITY));
+    }
+
+    #[test]
+    fn rays_inside_a_face_plane_hit() {
+        let aabb = unit_box();
+        let along_face = Ray::new(Point3::new(-1.0, 0.0, 0.5), Vector3::unit_x());
+        assert!(aabb.hit(&along_face, 0.0..Float::INFINITY));
+        let outside_face = Ray::new(Point3::new(-1.0, -0.001, 0.5), Vector3::unit_x());
+        assert!(!aabb.hit(&outside_face, 0.0..Float::INFINITY));
+    }
+
+    #[test]
+    fn hits_respect_the_t_range() {
+        let ray = Ray::new(Point3::new(-1.0, 0.5, 0.5), Vector3::unit_x());
+        assert!(unit_box().hit(&ray, 1.5..1.6));
+        assert!(!unit_box().hit(&ray, 2.5..3.0));
+        assert!(!unit_box().hit(&ray, 0.0..0.5));
+    }
+
+    #[test]
+    fn surrounding_box_covers_both() {
+        let other = Aabb::new(Point3::new(-1.0, 0.5, 2.0), Point3::new(0.5, 3.0, 4.0));
+        assert_eq!(
+            surrounding_box(&unit_box(), &other),
+            Aabb::new(Point3::new(-1.0, 0.0, 0.0), Point3::new(1.0, 3.0, 4.0))
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use cgmath::{InnerSpace, Point3, Vector3};
+
+use crate::{hittable::sphere_uv, texture::Texture, Color, Float};
+
+/// Radiance of rays that escape the scene
+pub enum Background {
+    SolidColor(Color),
+    /// Vertical blend from `bottom` (looking straight down) to `top` (looking straight up)
+    Gradient {
+        top: Color,
+        bottom: Color,
+    },
+    /// Equirectangular environment map, e.g. an HDR `ImageTexture`, for image-based lighting
+    Environment(Arc<dyn Texture>),
+}
+
+impl Background {
+    pub fn color(&self, direction: Vector3<Float>) -> Color {
+        match self {
+            Self::SolidColor(color) => *color,
+            Self::Gradient { top, bottom } => {
+                let t = (direction.normalize().y + 1.0) / 2.0;
+                (1.0 - t) * bottom + t * top
+            }
+            Self::Environment(texture) => {
+                let direction = direction.normalize();
+                let (u, v) = sphere_uv(direction);
+                texture.value(u, v, Point3::new(direction.x, direction.y, direction.z))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::ImageTexture;
+
+    #[test]
+    fn gradient_blends_from_bottom_to_top() {
+        let sky = Background::Gradient {
+            top: Color::new(0.5, 0.7, 1.0),
+            bottom: Color::new(1.0, 1.0, 1.0),
+        };
+        for &direction in &[
+            Vector3::new(0.3, -0.4, 2.0),
+            Vector3::new(0.0, 5.0, 0.0),
+            Vector3::new(1.0, -1.0, 0.0),
+        ] {
+            let t = 0.5 * (direction.normalize().y + 1.0);
+            let expected = (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0);
+            assert!((sky.color(direction) - expected).magnitude() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn environment_poles_sample_the_top_and_bottom_rows() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/corners.png");
+        let environment = Background::Environment(Arc::new(ImageTexture::new(path).unwrap()));
+        let top_row = [Color::new(1.0, 0.0, 0.0), Color::new(0.0, 1.0, 0.0)];
+        let bottom_row = [Color::new(0.0, 0.0, 1.0), Color::new(1.0, 1.0, 1.0)];
+        for &up in &[
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, 3.0, -0.0),
+            Vector3::new(1e-9, 1.0, -1e-9),
+        ] {
+            assert!(top_row.contains(&environment.color(up)), "{:?}", up);
+            assert!(bottom_row.contains(&environment.color(-up)), "{:?}", -up);
+        }
+    }
+}
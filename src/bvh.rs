@@ -0,0 +1,186 @@
+use std::{ops::Range, sync::Arc};
+
+use rand::{distributions::Uniform, prelude::Distribution, Rng};
+
+use crate::{
+    aabb::{surrounding_box, Aabb},
+    hittable::{HitRecord, Hittable},
+    Float, Ray,
+};
+
+/// Bounding volume hierarchy node
+pub struct BvhNode {
+    left: Arc<dyn Hittable>,
+    right: Arc<dyn Hittable>,
+    /// `None` at a root that also holds unbounded objects
+    bounding_box: Option<Aabb>,
+}
+
+impl BvhNode {
+    /// Builds a hierarchy by recursively splitting `objects` in half along a random axis.
+    ///
+    /// Unbounded objects such as planes are kept out of the hierarchy and tested on every ray.
+    /// Panics if `objects` is empty.
+    pub fn new<R: Rng>(objects: Vec<Arc<dyn Hittable>>, rng: &mut R) -> Self {
+        let (bounded, unbounded): (Vec<_>, Vec<_>) = objects
+            .into_iter()
+            .partition(|object| object.bounding_box().is_some());
+        if unbounded.is_empty() {
+            return Self::new_bounded(bounded, rng);
+        }
+
+        let unbounded = Arc::new(unbounded) as Arc<dyn Hittable>;
+        let bounded = if bounded.is_empty() {
+            unbounded.clone()
+        } else {
+            Arc::new(Self::new_bounded(bounded, rng)) as Arc<dyn Hittable>
+        };
+        Self {
+            left: unbounded,
+            right: bounded,
+            bounding_box: None,
+        }
+    }
+
+    fn new_bounded<R: Rng>(mut objects: Vec<Arc<dyn Hittable>>, rng: &mut R) -> Self {
+        let axis = Uniform::from(0..3).sample(rng);
+        let key = |object: &Arc<dyn Hittable>| {
+            object
+                .bounding_box()
+                .expect("No bounding box in BvhNode::new")
+                .min[axis]
+        };
+        let (left, right) = match objects.len() {
+            0 => panic!("BvhNode::new needs at least one object"),
+            1 => (objects[0].clone(), objects[0].clone()),
+            2 => {
+                if key(&objects[0]) <= key(&objects[1]) {
+                    (objects[0].clone(), objects[1].clone())
+                } else {
+                    (objects[1].clone(), objects[0].clone())
+                }
+            }
+            _ => {
+                objects.sort_by(|a, b| {
+                    key(a)
+                        .partial_cmp(&key(b))
+                        .expect("Bounding boxes could not be compared")
+                });
+                let right = objects.split_off(objects.len() / 2);
+                (
+                    Arc::new(Self::new_bounded(objects, rng)) as Arc<dyn Hittable>,
+                    Arc::new(Self::new_bounded(right, rng)) as Arc<dyn Hittable>,
+                )
+            }
+        };
+        let bounding_box = surrounding_box(
+            &left
+                .bounding_box()
+                .expect("No bounding box in BvhNode::new"),
+            &right
+                .bounding_box()
+                .expect("No bounding box in BvhNode::new"),
+        );
+        Self {
+            left,
+            right,
+            bounding_box: Some(bounding_box),
+        }
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, ray: &Ray, t_range: Range<Float>) -> Option<HitRecord<'_>> {
+        if self
+            .bounding_box
+            .is_some_and(|bounding_box| !bounding_box.hit(ray, t_range.clone()))
+        {
+            return None;
+        }
+        let left = self.left.hit(ray, t_range.clone());
+        let t_max = left.as_ref().map_or(t_range.end, |record| record.t);
+        let right = self.right.hit(ray, t_range.start..t_max);
+        right.or(left)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bounding_box
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Point3, Vector3};
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use super::*;
+    use crate::{
+        hittable::{Plane, Sphere},
+        material::Material,
+        Color,
+    };
+
+    fn random_spheres<R: Rng>(rng: &mut R) -> Vec<Arc<dyn Hittable>> {
+        (0..100)
+            .map(|_| {
+                let center = Point3::new(
+                    rng.gen_range(-10.0..10.0),
+                    rng.gen_range(-10.0..10.0),
+                    rng.gen_range(-10.0..10.0),
+                );
+                let material = Material::new_lambertian(Color::new(0.5, 0.5, 0.5));
+                Arc::new(Sphere::new(center, rng.gen_range(0.1..1.0), material))
+                    as Arc<dyn Hittable>
+            })
+            .collect()
+    }
+
+    fn random_ray<R: Rng>(rng: &mut R) -> Ray {
+        let mut coordinate = || rng.gen_range(-1.0..1.0);
+        Ray::new(
+            Point3::new(
+                15.0 * coordinate(),
+                15.0 * coordinate(),
+                15.0 * coordinate(),
+            ),
+            Vector3::new(coordinate(), coordinate(), coordinate()),
+        )
+    }
+
+    #[test]
+    fn finds_the_same_closest_hits_as_a_list() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let objects = random_spheres(&mut rng);
+        let bvh = BvhNode::new(objects.clone(), &mut rng);
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let ray = random_ray(&mut rng);
+            let expected = objects
+                .hit(&ray, 0.001..Float::INFINITY)
+                .map(|record| record.t);
+            assert_eq!(
+                bvh.hit(&ray, 0.001..Float::INFINITY).map(|record| record.t),
+                expected
+            );
+            hits += expected.is_some() as usize;
+        }
+        assert!(hits > 100);
+    }
+
+    #[test]
+    fn unbounded_objects_are_hit_far_outside_the_hierarchy() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut objects = random_spheres(&mut rng);
+        let material = Material::new_lambertian(Color::new(0.5, 0.5, 0.5));
+        objects.push(Arc::new(Plane::new(
+            Point3::new(0.0, -100.0, 0.0),
+            Vector3::unit_y(),
+            material,
+        )));
+        let bvh = BvhNode::new(objects, &mut rng);
+        assert_eq!(bvh.bounding_box(), None);
+        let ray = Ray::new(Point3::new(1000.0, 0.0, 1000.0), -Vector3::unit_y());
+        let record = bvh.hit(&ray, 0.001..Float::INFINITY).unwrap();
+        assert!((record.t - 100.0).abs() < 1e-3);
+    }
+}
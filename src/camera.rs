@@ -1,27 +1,75 @@
-use cgmath::{InnerSpace, Point3, Vector3};
-use rand::{distributions::Uniform, prelude::Distribution, Rng};
+use cgmath::{InnerSpace, Point3, Quaternion, Rad, Rotation, Rotation3, Vector3, Zero};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-use crate::Ray;
+use crate::{description::xyz, random::random_in_unit_disk, Float, Ray};
 
+/// Constructor inputs of a `Camera`, which is what it is serialized as
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "projection", rename_all = "snake_case", deny_unknown_fields)]
+pub enum CameraParameters {
+    Perspective {
+        #[serde(with = "xyz")]
+        position: Point3<Float>,
+        #[serde(with = "xyz")]
+        look_at: Point3<Float>,
+        #[serde(with = "xyz")]
+        up: Vector3<Float>,
+        vertical_fov: Float,
+        aspect_ratio: Float,
+        aperture: Float,
+        focus_distance: Float,
+        time0: Float,
+        time1: Float,
+        /// See `Camera::with_focus_tilt`
+        #[serde(with = "xyz", default = "Vector3::zero")]
+        focus_tilt: Vector3<Float>,
+    },
+    Orthographic {
+        #[serde(with = "xyz")]
+        position: Point3<Float>,
+        #[serde(with = "xyz")]
+        look_at: Point3<Float>,
+        #[serde(with = "xyz")]
+        up: Vector3<Float>,
+        ortho_height: Float,
+        aspect_ratio: Float,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "CameraParameters", into = "CameraParameters")]
 pub struct Camera {
-    origin: Point3<f64>,
-    lower_left_corner: Point3<f64>,
-    horizontal: Vector3<f64>,
-    vertical: Vector3<f64>,
-    u: Vector3<f64>,
-    v: Vector3<f64>,
-    lens_radius: f64,
+    parameters: CameraParameters,
+    origin: Point3<Float>,
+    lower_left_corner: Point3<Float>,
+    horizontal: Vector3<Float>,
+    vertical: Vector3<Float>,
+    u: Vector3<Float>,
+    v: Vector3<Float>,
+    w: Vector3<Float>,
+    /// Parallel rays from across the viewport instead of rays through `origin`
+    orthographic: bool,
+    lens_radius: Float,
+    focus_point: Point3<Float>,
+    focus_normal: Vector3<Float>,
+    /// Shutter open/close times
+    time0: Float,
+    time1: Float,
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        position: Point3<f64>,
-        at: Point3<f64>,
-        up: Vector3<f64>,
-        vertical_fov: f64,
-        aspect_ratio: f64,
-        aperture: f64,
-        focus_distance: f64,
+        position: Point3<Float>,
+        at: Point3<Float>,
+        up: Vector3<Float>,
+        vertical_fov: Float,
+        aspect_ratio: Float,
+        aperture: Float,
+        focus_distance: Float,
+        time0: Float,
+        time1: Float,
     ) -> Self {
         let theta = vertical_fov.to_radians();
         let h = (theta / 2.0).tan();
@@ -37,35 +85,383 @@ impl Camera {
         let vertical = focus_distance * viewport_height * v;
         let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0 - focus_distance * w;
         let lens_radius = aperture / 2.0;
+        let focus_point = origin - focus_distance * w;
         Self {
+            parameters: CameraParameters::Perspective {
+                position,
+                look_at: at,
+                up,
+                vertical_fov,
+                aspect_ratio,
+                aperture,
+                focus_distance,
+                time0,
+                time1,
+                focus_tilt: Vector3::zero(),
+            },
             origin,
             horizontal,
             vertical,
             lower_left_corner,
             u,
             v,
+            w,
+            orthographic: false,
             lens_radius,
+            focus_point,
+            focus_normal: w,
+            time0,
+            time1,
         }
     }
 
-    pub fn ray<R: Rng>(&self, s: f64, t: f64, rng: &mut R) -> Ray {
-        let rd = self.lens_radius * random_vector_in_unit_disk(rng);
+    /// Creates a parallel-projection camera whose viewport is `ortho_height` tall and centered
+    /// on `position`. Everything is in focus.
+    pub fn new_orthographic(
+        position: Point3<Float>,
+        at: Point3<Float>,
+        up: Vector3<Float>,
+        ortho_height: Float,
+        aspect_ratio: Float,
+    ) -> Self {
+        let w = (position - at).normalize();
+        let u = up.cross(w).normalize();
+        let v = w.cross(u);
+
+        let horizontal = aspect_ratio * ortho_height * u;
+        let vertical = ortho_height * v;
+        Self {
+            parameters: CameraParameters::Orthographic {
+                position,
+                look_at: at,
+                up,
+                ortho_height,
+                aspect_ratio,
+            },
+            origin: position,
+            horizontal,
+            vertical,
+            lower_left_corner: position - horizontal / 2.0 - vertical / 2.0,
+            u,
+            v,
+            w,
+            orthographic: true,
+            lens_radius: 0.0,
+            focus_point: position,
+            focus_normal: w,
+            time0: 0.0,
+            time1: 0.0,
+        }
+    }
+
+    /// Tilts the plane of sharp focus for tilt-shift effects.
+    ///
+    /// `focus_tilt` is a rotation vector in world space: its direction is the rotation axis
+    /// and its length the angle in radians. The plane keeps passing through the point at
+    /// `focus_distance` along the view axis. Replaces any earlier tilt. Rays that would meet
+    /// the plane nearly edge-on or behind the lens stay focused at `focus_distance`.
+    pub fn with_focus_tilt(mut self, focus_tilt: Vector3<Float>) -> Self {
+        let angle = focus_tilt.magnitude();
+        let normal = if angle > 0.0 {
+            let rotation = Quaternion::from_axis_angle(focus_tilt / angle, Rad(angle));
+            rotation.rotate_vector(self.w)
+        } else {
+            self.w
+        };
+        // the same plane either way; facing it like `w` keeps `ray`'s sign checks simple
+        self.focus_normal = if normal.dot(self.w) < 0.0 {
+            -normal
+        } else {
+            normal
+        };
+        if let CameraParameters::Perspective {
+            focus_tilt: tilt, ..
+        } = &mut self.parameters
+        {
+            *tilt = focus_tilt;
+        }
+        self
+    }
+
+    /// Rebuilds the camera with another width-to-height ratio, keeping everything else.
+    pub fn with_aspect_ratio(self, aspect_ratio: Float) -> Self {
+        let mut parameters = self.parameters;
+        match &mut parameters {
+            CameraParameters::Perspective {
+                aspect_ratio: ratio,
+                ..
+            }
+            | CameraParameters::Orthographic {
+                aspect_ratio: ratio,
+                ..
+            } => *ratio = aspect_ratio,
+        }
+        parameters.into()
+    }
+
+    /// The inputs the camera was created from
+    pub fn parameters(&self) -> &CameraParameters {
+        &self.parameters
+    }
+
+    pub fn ray<R: Rng>(&self, s: Float, t: Float, rng: &mut R) -> Ray {
+        if self.orthographic {
+            let origin = self.lower_left_corner + s * self.horizontal + t * self.vertical;
+            return Ray::new(origin, -self.w).with_time(self.time0);
+        }
+
+        let rd = self.lens_radius * random_in_unit_disk(rng);
         let offset = self.u * rd.x + self.v * rd.y;
 
-        Ray::new(
-            self.origin + offset,
-            self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
-        )
+        let direction =
+            self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin;
+        let distance = self.focus_distance_along(direction);
+        let target = self.origin + distance * direction;
+        // a still camera draws no time sample, leaving the RNG stream untouched
+        let time = if self.time1 > self.time0 {
+            rng.gen_range(self.time0..self.time1)
+        } else {
+            self.time0
+        };
+        Ray::new(self.origin + offset, target - self.origin - offset).with_time(time)
+    }
+
+    /// How far along `direction` the ray through the lens center meets the plane of focus
+    fn focus_distance_along(&self, direction: Vector3<Float>) -> Float {
+        /// Cosine between a ray and a tilted plane of focus below which the tilt is ignored
+        const MIN_FOCUS_COSINE: Float = 1e-3;
+
+        let to_focus_point = self.focus_point - self.origin;
+        let cosine = -direction.normalize().dot(self.focus_normal);
+        let distance = to_focus_point.dot(self.focus_normal) / direction.dot(self.focus_normal);
+        if cosine > MIN_FOCUS_COSINE && distance > 0.0 {
+            distance
+        } else {
+            to_focus_point.dot(self.w) / direction.dot(self.w)
+        }
+    }
+}
+
+impl From<CameraParameters> for Camera {
+    fn from(parameters: CameraParameters) -> Self {
+        match parameters {
+            CameraParameters::Perspective {
+                position,
+                look_at,
+                up,
+                vertical_fov,
+                aspect_ratio,
+                aperture,
+                focus_distance,
+                time0,
+                time1,
+                focus_tilt,
+            } => Self::new(
+                position,
+                look_at,
+                up,
+                vertical_fov,
+                aspect_ratio,
+                aperture,
+                focus_distance,
+                time0,
+                time1,
+            )
+            .with_focus_tilt(focus_tilt),
+            CameraParameters::Orthographic {
+                position,
+                look_at,
+                up,
+                ortho_height,
+                aspect_ratio,
+            } => Self::new_orthographic(position, look_at, up, ortho_height, aspect_ratio),
+        }
     }
 }
 
-fn random_vector_in_unit_disk<R: Rng>(rng: &mut R) -> Vector3<f64> {
-    let distribution = Uniform::from(0.0..1.0);
-    loop {
-        let x = distribution.sample(rng);
-        let y = distribution.sample(rng);
-        if x * x + y * y <= 1.0 {
-            return Vector3::new(x, y, 0.0);
+impl From<Camera> for CameraParameters {
+    fn from(camera: Camera) -> Self {
+        camera.parameters
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use super::*;
+    use crate::consts;
+
+    fn lens_camera() -> Camera {
+        Camera::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 0.0, -1.0),
+            Vector3::unit_y(),
+            40.0,
+            1.0,
+            0.5,
+            10.0,
+            0.0,
+            0.0,
+        )
+    }
+
+    /// Largest distance between the points where rays through `(s, t)` cross `z = -depth`
+    fn blur(camera: &Camera, s: Float, t: Float, depth: Float) -> Float {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let points: Vec<_> = (0..64)
+            .map(|_| {
+                let ray = camera.ray(s, t, &mut rng);
+                ray.at((-depth - ray.origin.z) / ray.direction.z)
+            })
+            .collect();
+        points
+            .iter()
+            .map(|point| (point - points[0]).magnitude())
+            .fold(0.0, Float::max)
+    }
+
+    #[test]
+    fn tilted_focus_blurs_one_height_of_the_same_depth() {
+        let camera = lens_camera();
+        assert!(blur(&camera, 0.5, 0.5, 10.0) < 1e-4);
+        assert!(blur(&camera, 0.5, 0.9, 10.0) < 1e-4);
+
+        let tilted = lens_camera().with_focus_tilt(Vector3::new(0.3, 0.0, 0.0));
+        assert!(blur(&tilted, 0.5, 0.5, 10.0) < 1e-4);
+        assert!(blur(&tilted, 0.5, 0.9, 10.0) > 0.01);
+    }
+
+    #[test]
+    fn steep_focus_tilts_keep_rays_finite_and_in_front() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        for &angle in &[0.5, 1.2, 1.5, consts::FRAC_PI_2, 2.0, consts::PI] {
+            let camera = lens_camera().with_focus_tilt(Vector3::new(angle, 0.0, 0.0));
+            for i in 0..=10 {
+                let t = i as Float / 10.0;
+                let ray = camera.ray(0.5, t, &mut rng);
+                let direction = ray.direction.normalize();
+                assert!(direction.x.is_finite() && direction.y.is_finite());
+                assert!(direction.z < 0.0, "tilt {} t {}: {:?}", angle, t, direction);
+            }
+        }
+    }
+
+    fn shutter_camera(time0: Float, time1: Float) -> Camera {
+        Camera::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 0.0, -1.0),
+            Vector3::unit_y(),
+            40.0,
+            1.0,
+            0.0,
+            1.0,
+            time0,
+            time1,
+        )
+    }
+
+    #[test]
+    fn open_shutter_spreads_ray_times() {
+        let camera = shutter_camera(0.0, 1.0);
+        let mut rng = SmallRng::seed_from_u64(0);
+        let times: Vec<Float> = (0..10_000)
+            .map(|_| camera.ray(0.5, 0.5, &mut rng).time)
+            .collect();
+        let mean = times.iter().sum::<Float>() / times.len() as Float;
+        assert!((mean - 0.5).abs() < 0.02, "mean {}", mean);
+        assert!(times.iter().all(|time| (0.0..1.0).contains(time)));
+        assert!(times.iter().any(|&time| time < 0.1) && times.iter().any(|&time| time > 0.9));
+    }
+
+    #[test]
+    fn closed_shutter_matches_a_still_camera() {
+        let still = shutter_camera(0.0, 0.0);
+        let closed = shutter_camera(0.25, 0.25);
+        let (mut a, mut b) = (SmallRng::seed_from_u64(0), SmallRng::seed_from_u64(0));
+        for _ in 0..10 {
+            let (ray, other) = (still.ray(0.3, 0.6, &mut a), closed.ray(0.3, 0.6, &mut b));
+            assert_eq!(ray.direction, other.direction);
+            assert_eq!(other.time, 0.25);
+        }
+    }
+
+    #[test]
+    fn new_aspect_ratio_widens_the_viewport_only() {
+        let camera = shutter_camera(0.0, 0.0).with_focus_tilt(Vector3::new(0.1, 0.0, 0.0));
+        let wide = camera.clone().with_aspect_ratio(2.0);
+        assert!((wide.horizontal.magnitude() - 2.0 * camera.horizontal.magnitude()).abs() < 1e-6);
+        assert_eq!(wide.vertical, camera.vertical);
+        assert_eq!(wide.focus_normal, camera.focus_normal);
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel_and_spread_over_the_viewport() {
+        let camera = Camera::new_orthographic(
+            Point3::new(0.0, 0.0, 5.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::unit_y(),
+            2.0,
+            1.5,
+        );
+        let mut rng = SmallRng::seed_from_u64(0);
+        let corner = camera.ray(0.0, 0.0, &mut rng);
+        let center = camera.ray(0.5, 0.5, &mut rng);
+        let opposite = camera.ray(1.0, 1.0, &mut rng);
+        for ray in &[&corner, &center, &opposite] {
+            assert_eq!(ray.direction.normalize(), -Vector3::unit_z());
+        }
+        assert!((center.origin - Point3::new(0.0, 0.0, 5.0)).magnitude() < 1e-6);
+        let extent = opposite.origin - corner.origin;
+        assert!((extent - Vector3::new(3.0, 2.0, 0.0)).magnitude() < 1e-6);
+    }
+
+    #[test]
+    fn cameras_round_trip_through_their_parameters() {
+        for camera in [
+            lens_camera(),
+            lens_camera().with_focus_tilt(Vector3::new(0.2, 0.1, 0.0)),
+            shutter_camera(0.0, 1.0),
+            Camera::new_orthographic(
+                Point3::new(0.0, 5.0, 5.0),
+                Point3::new(0.0, 0.0, 0.0),
+                Vector3::unit_y(),
+                2.0,
+                1.5,
+            ),
+        ] {
+            let json = serde_json::to_string(&camera).unwrap();
+            assert!(!json.contains("lower_left_corner"));
+            assert_eq!(serde_json::from_str::<Camera>(&json).unwrap(), camera);
+            let parameters: CameraParameters = serde_json::from_str(&json).unwrap();
+            assert_eq!(&parameters, camera.parameters());
+        }
+    }
+
+    #[test]
+    fn pinhole_center_ray_follows_the_view_direction() {
+        let position = Point3::new(1.0, 2.0, 3.0);
+        let look_at = Point3::new(-2.0, 0.0, -1.0);
+        let camera = Camera::new(
+            position,
+            look_at,
+            Vector3::unit_y(),
+            60.0,
+            1.5,
+            0.0,
+            2.0,
+            0.0,
+            0.0,
+        );
+        let mut rng = SmallRng::seed_from_u64(0);
+        for _ in 0..8 {
+            let ray = camera.ray(0.5, 0.5, &mut rng);
+            assert_eq!(ray.origin, position);
+            let along = ray
+                .direction
+                .normalize()
+                .dot((look_at - position).normalize());
+            assert!((along - 1.0).abs() < 1e-6, "{}", along);
         }
     }
 }
@@ -0,0 +1,331 @@
+use std::{convert::TryFrom, sync::Arc};
+
+use cgmath::{Point3, Vector3};
+use rand::{rngs::SmallRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    background::Background,
+    bvh::BvhNode,
+    camera::{Camera, CameraParameters},
+    hittable::{Capsule, Hittable, HittableList, Plane, Sphere},
+    material::{LambertianMode, Material},
+    render::{Fog, LightSampling, RenderMode, RenderSettings, SampleStrategy},
+    scene::Scene,
+    texture::Texture,
+    Color, Float,
+};
+
+/// A scene as written in a JSON file; vectors, points and colors are `[x, y, z]` arrays and
+/// enums are objects tagged with a snake_case `"type"`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneDescription {
+    pub width: usize,
+    pub height: usize,
+    pub samples_per_pixel: usize,
+    pub max_depth: usize,
+    /// Its `aspect_ratio` is replaced by `width / height`
+    pub camera: CameraParameters,
+    pub background: BackgroundDescription,
+    pub objects: Vec<ObjectDescription>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum BackgroundDescription {
+    SolidColor { color: [Float; 3] },
+    Gradient { top: [Float; 3], bottom: [Float; 3] },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ObjectDescription {
+    Sphere(Sphere),
+    Plane {
+        point: [Float; 3],
+        normal: [Float; 3],
+        material: MaterialDescription,
+    },
+    /// A cylinder from `a` to `b` with hemispherical caps
+    Capsule {
+        a: [Float; 3],
+        b: [Float; 3],
+        radius: Float,
+        material: MaterialDescription,
+    },
+}
+
+/// Serialized form of a `Material`; only materials without textures have one.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum MaterialDescription {
+    Lambertian {
+        albedo: [Float; 3],
+        #[serde(default = "default_lambertian_mode")]
+        mode: LambertianMode,
+    },
+    Metal {
+        albedo: [Float; 3],
+        fuzz: Float,
+    },
+    Dielectric {
+        index_of_refraction: Float,
+        #[serde(default = "default_tint_internal_reflections")]
+        tint_internal_reflections: bool,
+        #[serde(default)]
+        absorption: [Float; 3],
+    },
+    Transparent,
+    DiffuseLight {
+        emit: [Float; 3],
+    },
+    Isotropic {
+        albedo: [Float; 3],
+    },
+}
+
+fn default_lambertian_mode() -> LambertianMode {
+    LambertianMode::UnitVector
+}
+
+fn default_tint_internal_reflections() -> bool {
+    true
+}
+
+/// `#[serde(with = "xyz")]` for cgmath points and vectors written as `[x, y, z]` arrays
+pub mod xyz {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::Float;
+
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Copy + Into<[Float; 3]>,
+        S: Serializer,
+    {
+        let array: [Float; 3] = (*value).into();
+        array.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: From<[Float; 3]>,
+        D: Deserializer<'de>,
+    {
+        <[Float; 3]>::deserialize(deserializer).map(T::from)
+    }
+}
+
+impl SceneDescription {
+    /// Builds the scene, with the render settings not in the file as in `Scene::random_spheres`.
+    pub fn into_scene(self) -> Scene {
+        let mut hittables = HittableList::new();
+        for object in self.objects {
+            match object {
+                ObjectDescription::Sphere(sphere) => hittables.add(sphere),
+                ObjectDescription::Plane {
+                    point,
+                    normal,
+                    material,
+                } => hittables.add(Plane::new(
+                    Point3::from(point),
+                    Vector3::from(normal),
+                    material.into(),
+                )),
+                ObjectDescription::Capsule {
+                    a,
+                    b,
+                    radius,
+                    material,
+                } => hittables.add(Capsule::new(
+                    Point3::from(a),
+                    Point3::from(b),
+                    radius,
+                    material.into(),
+                )),
+            }
+        }
+        // a hierarchy needs at least one object; without any only the background shows
+        let world: Arc<dyn Hittable> = if hittables.is_empty() {
+            Arc::new(hittables)
+        } else {
+            let mut rng = SmallRng::seed_from_u64(0);
+            Arc::new(BvhNode::new(hittables.into_objects(), &mut rng))
+        };
+
+        let camera =
+            Camera::from(self.camera).with_aspect_ratio(self.width as Float / self.height as Float);
+
+        let background = match self.background {
+            BackgroundDescription::SolidColor { color } => {
+                Background::SolidColor(Color::from(color))
+            }
+            BackgroundDescription::Gradient { top, bottom } => Background::Gradient {
+                top: Color::from(top),
+                bottom: Color::from(bottom),
+            },
+        };
+
+        let settings = RenderSettings {
+            width: self.width,
+            height: self.height,
+            samples_per_pixel: self.samples_per_pixel,
+            max_depth: self.max_depth,
+            fog: Fog {
+                density: 0.0,
+                color: Color::new(0.0, 0.0, 0.0),
+            },
+            sample_strategy: SampleStrategy::Stratified,
+            russian_roulette_depth: Some(5),
+            clamp_radiance: None,
+            seed: 0,
+            adaptive_sampling: None,
+            lights: None,
+            light_sampling: LightSampling::Brdf,
+            mode: RenderMode::Beauty,
+        };
+
+        Scene {
+            world,
+            camera,
+            background,
+            settings,
+        }
+    }
+}
+
+impl From<MaterialDescription> for Material {
+    fn from(description: MaterialDescription) -> Self {
+        match description {
+            MaterialDescription::Lambertian { albedo, mode } => {
+                Self::new_lambertian(Color::from(albedo)).with_lambertian_mode(mode)
+            }
+            MaterialDescription::Metal { albedo, fuzz } => {
+                Self::new_metal(Color::from(albedo), fuzz)
+            }
+            MaterialDescription::Dielectric {
+                index_of_refraction,
+                tint_internal_reflections,
+                absorption,
+            } => Self::Dielectric {
+                index_of_refraction,
+                tint_internal_reflections,
+                absorption: Color::from(absorption),
+            },
+            MaterialDescription::Transparent => Self::new_transparent(),
+            MaterialDescription::DiffuseLight { emit } => {
+                Self::new_diffuse_light(Color::from(emit))
+            }
+            MaterialDescription::Isotropic { albedo } => Self::new_isotropic(Color::from(albedo)),
+        }
+    }
+}
+
+impl TryFrom<&Material> for MaterialDescription {
+    type Error = &'static str;
+
+    /// Fails for materials with textures other than solid colors.
+    fn try_from(material: &Material) -> Result<Self, Self::Error> {
+        let solid = |texture: &Arc<dyn Texture>| {
+            texture
+                .solid_color()
+                .map(Into::into)
+                .ok_or("only solid color textures can be serialized")
+        };
+        Ok(match material {
+            Material::Lambertian { albedo, mode } => Self::Lambertian {
+                albedo: solid(albedo)?,
+                mode: *mode,
+            },
+            Material::Metal { albedo, fuzz } => {
+                let [r, g, b] = solid(fuzz)?;
+                Self::Metal {
+                    albedo: (*albedo).into(),
+                    fuzz: (r + g + b) / 3.0,
+                }
+            }
+            Material::Dielectric {
+                index_of_refraction,
+                tint_internal_reflections,
+                absorption,
+            } => Self::Dielectric {
+                index_of_refraction: *index_of_refraction,
+                tint_internal_reflections: *tint_internal_reflections,
+                absorption: (*absorption).into(),
+            },
+            Material::Transparent => Self::Transparent,
+            Material::DiffuseLight { emit } => Self::DiffuseLight {
+                emit: (*emit).into(),
+            },
+            Material::Isotropic { albedo } => Self::Isotropic {
+                albedo: (*albedo).into(),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Ray;
+
+    fn example() -> &'static str {
+        include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/scenes/three_spheres.json"
+        ))
+    }
+
+    #[test]
+    fn example_scene_round_trips_through_json() {
+        let description: SceneDescription = serde_json::from_str(example()).unwrap();
+        assert_eq!(description.objects.len(), 4);
+        let json = serde_json::to_string(&description).unwrap();
+        assert_eq!(
+            serde_json::from_str::<SceneDescription>(&json).unwrap(),
+            description
+        );
+    }
+
+    #[test]
+    fn unknown_types_and_fields_are_errors() {
+        let with = |from: &str, to: &str| {
+            assert!(example().contains(from));
+            serde_json::from_str::<SceneDescription>(&example().replacen(from, to, 1))
+        };
+        let error = with("\"type\": \"metal\"", "\"type\": \"chrome\"").unwrap_err();
+        assert!(error.to_string().contains("chrome"), "{}", error);
+        let error = with("\"type\": \"plane\"", "\"type\": \"torus\"").unwrap_err();
+        assert!(error.to_string().contains("torus"), "{}", error);
+        assert!(with("\"radius\"", "\"radius\": 1.0, \"spin\"").is_err());
+    }
+
+    #[test]
+    fn scene_without_objects_shows_only_the_background() {
+        let mut description: SceneDescription = serde_json::from_str(example()).unwrap();
+        description.objects.clear();
+        let scene = description.into_scene();
+        let ray = Ray::new(Point3::new(13.0, 3.0, 2.0), -Vector3::unit_x());
+        assert!(scene.world.hit(&ray, 0.001..Float::INFINITY).is_none());
+    }
+
+    #[test]
+    fn capsules_load_from_json() {
+        let mut description: SceneDescription = serde_json::from_str(example()).unwrap();
+        description.objects = vec![serde_json::from_str(
+            r#"{
+                "type": "capsule",
+                "a": [0.0, 0.0, 0.0],
+                "b": [0.0, 2.0, 0.0],
+                "radius": 0.5,
+                "material": { "type": "metal", "albedo": [0.8, 0.8, 0.8], "fuzz": 0.0 }
+            }"#,
+        )
+        .unwrap()];
+        let scene = description.into_scene();
+        let ray = Ray::new(Point3::new(5.0, 2.2, 0.0), -Vector3::unit_x());
+        let record = scene.world.hit(&ray, 0.001..Float::INFINITY).unwrap();
+        assert!((record.p.x - (0.25 - 0.04 as Float).sqrt()).abs() < 1e-5);
+    }
+}
@@ -1,48 +1,250 @@
-use std::ops::RangeBounds;
+use std::{ops::Range, sync::Arc};
 
-use cgmath::{InnerSpace, Point3, Vector3};
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
 
-use crate::{material::Material, Ray};
+use crate::{
+    aabb::{surrounding_box, Aabb},
+    consts::PI,
+    description::xyz,
+    material::Material,
+    texture::Texture,
+    Float, Ray,
+};
 
 pub struct HitRecord<'material> {
-    pub p: Point3<f64>,
-    pub normal: Vector3<f64>,
+    pub p: Point3<Float>,
+    pub normal: Vector3<Float>,
     pub material: &'material Material,
-    pub t: f64,
+    pub t: Float,
     pub front_face: bool,
+    /// Surface coordinates of the hit, e.g. barycentric for triangles
+    pub u: Float,
+    pub v: Float,
+    /// Near clip to use for rays spawned from this hit, to avoid self-intersection. Spheres and
+    /// triangles can override it with `with_epsilon`; other primitives use `DEFAULT_EPSILON`.
+    pub t_min: Float,
+    /// Unit tangent along increasing `u` and bitangent along increasing `v`, for normal mapping;
+    /// `None` for primitives without one
+    pub tangent_frame: Option<(Vector3<Float>, Vector3<Float>)>,
 }
 
-pub trait Hittable {
-    fn hit<R: Clone + RangeBounds<f64> + Sync>(&self, ray: &Ray, t_range: R) -> Option<HitRecord>;
+/// Near clip used for secondary rays unless a primitive overrides it
+pub const DEFAULT_EPSILON: Float = 0.001;
+
+impl<'material> HitRecord<'material> {
+    /// Creates a record at `ray.at(t)`, flipping `outward_normal` to face against the ray.
+    pub fn new(
+        ray: &Ray,
+        t: Float,
+        outward_normal: Vector3<Float>,
+        material: &'material Material,
+    ) -> Self {
+        let front_face = ray.direction.dot(outward_normal) < 0.0;
+        Self {
+            p: ray.at(t),
+            normal: if front_face {
+                outward_normal
+            } else {
+                -outward_normal
+            },
+            material,
+            t,
+            front_face,
+            u: 0.0,
+            v: 0.0,
+            t_min: DEFAULT_EPSILON,
+            tangent_frame: None,
+        }
+    }
+}
+
+pub trait Hittable: Send + Sync {
+    fn hit(&self, ray: &Ray, t_range: Range<Float>) -> Option<HitRecord<'_>>;
+
+    /// `None` if the object is unbounded or empty
+    fn bounding_box(&self) -> Option<Aabb>;
+
+    /// Solid-angle density with which `random` picks `direction` from `origin`; 0 for objects
+    /// that cannot be sampled as lights.
+    fn pdf_value(&self, _origin: Point3<Float>, _direction: Vector3<Float>) -> Float {
+        0.0
+    }
+
+    /// Direction from `origin` towards a point on the object, chosen with the uniform samples
+    /// `u` and `v` in `[0, 1)`.
+    fn random(&self, _origin: Point3<Float>, _u: Float, _v: Float) -> Vector3<Float> {
+        Vector3::unit_x()
+    }
 }
 
-impl<H: Hittable + Send + Sync> Hittable for Vec<H> {
-    fn hit<R: Clone + RangeBounds<f64> + Sync>(&self, ray: &Ray, t_range: R) -> Option<HitRecord> {
+impl<H: Hittable> Hittable for Vec<H> {
+    fn hit(&self, ray: &Ray, t_range: Range<Float>) -> Option<HitRecord<'_>> {
         self.par_iter()
             .filter_map(|hittable| hittable.hit(ray, t_range.clone()))
             .min_by(|a, b| a.t.partial_cmp(&b.t).expect("Hit objects did not found"))
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let mut boxes = self.iter().map(|hittable| hittable.bounding_box());
+        let first = boxes.next()??;
+        boxes.try_fold(first, |accumulated, bounding_box| {
+            Some(surrounding_box(&accumulated, &bounding_box?))
+        })
+    }
+}
+
+impl<H: Hittable + ?Sized> Hittable for Arc<H> {
+    fn hit(&self, ray: &Ray, t_range: Range<Float>) -> Option<HitRecord<'_>> {
+        self.as_ref().hit(ray, t_range)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.as_ref().bounding_box()
+    }
+
+    fn pdf_value(&self, origin: Point3<Float>, direction: Vector3<Float>) -> Float {
+        self.as_ref().pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Point3<Float>, u: Float, v: Float) -> Vector3<Float> {
+        self.as_ref().random(origin, u, v)
+    }
+}
+
+impl<H: Hittable> Hittable for Option<H> {
+    fn hit(&self, ray: &Ray, t_range: Range<Float>) -> Option<HitRecord<'_>> {
+        self.as_ref()
+            .and_then(|hittable| hittable.hit(ray, t_range))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.as_ref().and_then(|hittable| hittable.bounding_box())
+    }
+}
+
+/// Heterogeneous collection of objects, e.g. for building a scene before handing it to a BVH
+#[derive(Default)]
+pub struct HittableList {
+    objects: Vec<Arc<dyn Hittable>>,
+}
+
+impl HittableList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add<H: Hittable + 'static>(&mut self, object: H) {
+        self.objects.push(Arc::new(object));
+    }
+
+    pub fn clear(&mut self) {
+        self.objects.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    pub fn into_objects(self) -> Vec<Arc<dyn Hittable>> {
+        self.objects
+    }
+}
+
+impl Hittable for HittableList {
+    fn hit(&self, ray: &Ray, t_range: Range<Float>) -> Option<HitRecord<'_>> {
+        self.objects.hit(ray, t_range)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.objects.bounding_box()
+    }
+
+    /// Mean of the objects' densities, since `random` picks each of them equally often
+    fn pdf_value(&self, origin: Point3<Float>, direction: Vector3<Float>) -> Float {
+        if self.objects.is_empty() {
+            return 0.0;
+        }
+        self.objects
+            .iter()
+            .map(|object| object.pdf_value(origin, direction))
+            .sum::<Float>()
+            / self.objects.len() as Float
+    }
+
+    /// Uses `u` to pick one object uniformly, then rescales it to `[0, 1)` to sample that object.
+    fn random(&self, origin: Point3<Float>, u: Float, v: Float) -> Vector3<Float> {
+        if self.objects.is_empty() {
+            return Vector3::unit_x();
+        }
+        let scaled = u * self.objects.len() as Float;
+        let index = (scaled as usize).min(self.objects.len() - 1);
+        self.objects[index].random(origin, scaled - index as Float, v)
+    }
 }
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Sphere {
-    center: Point3<f64>,
-    radius: f64,
+    #[serde(with = "xyz")]
+    center: Point3<Float>,
+    radius: Float,
     material: Material,
+    #[serde(default)]
+    inverted: bool,
+    #[serde(default = "default_epsilon")]
+    epsilon: Float,
+}
+
+fn default_epsilon() -> Float {
+    DEFAULT_EPSILON
 }
 
 impl Sphere {
-    pub fn new(center: Point3<f64>, radius: f64, material: Material) -> Self {
+    pub fn new(center: Point3<Float>, radius: Float, material: Material) -> Self {
+        Self {
+            center,
+            radius,
+            material,
+            inverted: false,
+            epsilon: DEFAULT_EPSILON,
+        }
+    }
+
+    /// Creates a sphere whose normals point inward, so its inner surface is the front face.
+    pub fn new_inverted(center: Point3<Float>, radius: Float, material: Material) -> Self {
         Self {
             center,
             radius,
             material,
+            inverted: true,
+            epsilon: DEFAULT_EPSILON,
+        }
+    }
+
+    /// Overrides the near clip of rays spawned from this sphere's surface.
+    pub fn with_epsilon(mut self, epsilon: Float) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+
+    fn outward_normal(&self, p: Point3<Float>) -> Vector3<Float> {
+        let normal = (p - self.center) / self.radius;
+        if self.inverted {
+            -normal
+        } else {
+            normal
         }
     }
 }
 
 impl Hittable for Sphere {
-    fn hit<R: Clone + RangeBounds<f64>>(&self, ray: &Ray, t_range: R) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_range: Range<Float>) -> Option<HitRecord<'_>> {
         let vec_from_center = ray.origin - self.center;
         let a = ray.direction.dot(ray.direction);
         let half_b = vec_from_center.dot(ray.direction);
@@ -55,28 +257,38 @@ impl Hittable for Sphere {
             if t_range.contains(&root) {
                 let t = root;
                 let p = ray.at(t);
-                let normal = (p - self.center) / self.radius;
-                let front_face = ray.direction.dot((p - self.center) / self.radius) < 0.0;
+                let normal = self.outward_normal(p);
+                let (u, v) = sphere_uv((p - self.center) / self.radius);
+                let front_face = ray.direction.dot(normal) < 0.0;
                 Some(HitRecord {
                     p,
                     normal: if front_face { normal } else { -normal },
                     material: &self.material,
                     t,
                     front_face,
+                    u,
+                    v,
+                    t_min: self.epsilon,
+                    tangent_frame: Some(sphere_tangent_frame((p - self.center) / self.radius)),
                 })
             } else {
                 let root = (-half_b + discriminant.sqrt()) / a;
                 if t_range.contains(&root) {
                     let t = root;
                     let p = ray.at(t);
-                    let normal = (p - self.center) / self.radius;
-                    let front_face = ray.direction.dot((p - self.center) / self.radius) < 0.0;
+                    let normal = self.outward_normal(p);
+                    let (u, v) = sphere_uv((p - self.center) / self.radius);
+                    let front_face = ray.direction.dot(normal) < 0.0;
                     Some(HitRecord {
                         p,
                         normal: if front_face { normal } else { -normal },
                         material: &self.material,
                         t,
                         front_face,
+                        u,
+                        v,
+                        t_min: self.epsilon,
+                        tangent_frame: Some(sphere_tangent_frame((p - self.center) / self.radius)),
                     })
                 } else {
                     None
@@ -84,4 +296,1410 @@ impl Hittable for Sphere {
             }
         }
     }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let radius = Vector3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(self.center - radius, self.center + radius))
+    }
+
+    /// Uniform over the cone of directions the sphere subtends from `origin`
+    fn pdf_value(&self, origin: Point3<Float>, direction: Vector3<Float>) -> Float {
+        let distance2 = (self.center - origin).magnitude2();
+        if distance2 <= self.radius * self.radius
+            || self
+                .hit(
+                    &Ray::new(origin, direction),
+                    DEFAULT_EPSILON..Float::INFINITY,
+                )
+                .is_none()
+        {
+            return 0.0;
+        }
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance2).sqrt();
+        1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+    }
+
+    fn random(&self, origin: Point3<Float>, u: Float, v: Float) -> Vector3<Float> {
+        let to_center = self.center - origin;
+        let distance2 = to_center.magnitude2();
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance2)
+            .max(0.0)
+            .sqrt();
+        let z = 1.0 + v * (cos_theta_max - 1.0);
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * u;
+        let w = to_center.normalize();
+        let a = if w.x.abs() > 0.9 {
+            Vector3::unit_y()
+        } else {
+            Vector3::unit_x()
+        };
+        let bitangent = w.cross(a).normalize();
+        let tangent = w.cross(bitangent);
+        r * phi.cos() * tangent + r * phi.sin() * bitangent + z * w
+    }
+}
+
+/// A sphere whose center moves linearly from `center0` at `time0` to `center1` at `time1`.
+pub struct MovingSphere {
+    center0: Point3<Float>,
+    center1: Point3<Float>,
+    time0: Float,
+    time1: Float,
+    radius: Float,
+    material: Material,
+}
+
+impl MovingSphere {
+    pub fn new(
+        center0: Point3<Float>,
+        center1: Point3<Float>,
+        time0: Float,
+        time1: Float,
+        radius: Float,
+        material: Material,
+    ) -> Self {
+        Self {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            material,
+        }
+    }
+
+    pub fn center(&self, time: Float) -> Point3<Float> {
+        if self.time1 == self.time0 {
+            self.center0
+        } else {
+            self.center0
+                + ((time - self.time0) / (self.time1 - self.time0)) * (self.center1 - self.center0)
+        }
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, ray: &Ray, t_range: Range<Float>) -> Option<HitRecord<'_>> {
+        let center = self.center(ray.time);
+        let vec_from_center = ray.origin - center;
+        let t = solve_quadratic(
+            ray.direction.dot(ray.direction),
+            vec_from_center.dot(ray.direction),
+            vec_from_center.dot(vec_from_center) - self.radius * self.radius,
+        )
+        .iter()
+        .flatten()
+        .copied()
+        .find(|t| t_range.contains(t))?;
+        let outward_normal = (ray.at(t) - center) / self.radius;
+        let mut record = HitRecord::new(ray, t, outward_normal, &self.material);
+        let (u, v) = sphere_uv(outward_normal);
+        record.u = u;
+        record.v = v;
+        Some(record)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let radius = Vector3::new(self.radius, self.radius, self.radius);
+        Some(surrounding_box(
+            &Aabb::new(self.center0 - radius, self.center0 + radius),
+            &Aabb::new(self.center1 - radius, self.center1 + radius),
+        ))
+    }
+}
+
+/// A cylinder between `a` and `b` closed by hemispherical caps.
+pub struct Capsule {
+    a: Point3<Float>,
+    b: Point3<Float>,
+    radius: Float,
+    material: Material,
+}
+
+impl Capsule {
+    pub fn new(a: Point3<Float>, b: Point3<Float>, radius: Float, material: Material) -> Self {
+        Self {
+            a,
+            b,
+            radius,
+            material,
+        }
+    }
+
+    /// Position of `p` projected on the axis, 0 at `a` and 1 at `b`.
+    fn axis_parameter(&self, p: Point3<Float>) -> Float {
+        let axis = self.b - self.a;
+        (p - self.a).dot(axis) / axis.dot(axis)
+    }
+}
+
+impl Hittable for Capsule {
+    fn hit(&self, ray: &Ray, t_range: Range<Float>) -> Option<HitRecord<'_>> {
+        let axis = self.b - self.a;
+        let axis_length2 = axis.dot(axis);
+        let radius2 = self.radius * self.radius;
+        let mut closest: Option<(Float, Vector3<Float>)> = None;
+        let mut consider = |t: Float, outward_normal: Vector3<Float>| {
+            if t_range.contains(&t) && closest.is_none_or(|(closest_t, _)| t < closest_t) {
+                closest = Some((t, outward_normal));
+            }
+        };
+
+        // a capsule with `a == b` has no body and is just a sphere around `a`
+        let is_sphere = axis_length2 == 0.0;
+
+        // body: the ray and the offset from the axis, both with the axial part removed
+        if !is_sphere {
+            let direction = ray.direction - axis * (ray.direction.dot(axis) / axis_length2);
+            let offset =
+                (ray.origin - self.a) - axis * ((ray.origin - self.a).dot(axis) / axis_length2);
+            for t in solve_quadratic(
+                direction.dot(direction),
+                offset.dot(direction),
+                offset.dot(offset) - radius2,
+            )
+            .iter()
+            .flatten()
+            {
+                let p = ray.at(*t);
+                let s = self.axis_parameter(p);
+                if (0.0..=1.0).contains(&s) {
+                    consider(*t, (p - (self.a + s * axis)) / self.radius);
+                }
+            }
+        }
+
+        // caps
+        for (center, is_a) in [(self.a, true), (self.b, false)] {
+            let vec_from_center = ray.origin - center;
+            for t in solve_quadratic(
+                ray.direction.dot(ray.direction),
+                vec_from_center.dot(ray.direction),
+                vec_from_center.dot(vec_from_center) - radius2,
+            )
+            .iter()
+            .flatten()
+            {
+                let p = ray.at(*t);
+                let beyond_body = is_sphere || {
+                    let s = self.axis_parameter(p);
+                    (is_a && s < 0.0) || (!is_a && s > 1.0)
+                };
+                if beyond_body {
+                    consider(*t, (p - center) / self.radius);
+                }
+            }
+        }
+
+        closest.map(|(t, outward_normal)| HitRecord::new(ray, t, outward_normal, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let radius = Vector3::new(self.radius, self.radius, self.radius);
+        Some(surrounding_box(
+            &Aabb::new(self.a - radius, self.a + radius),
+            &Aabb::new(self.b - radius, self.b + radius),
+        ))
+    }
+}
+
+/// A cylinder of `height` rising from `base` along `axis`, optionally closed by flat caps.
+pub struct Cylinder {
+    base: Point3<Float>,
+    axis: Vector3<Float>,
+    radius: Float,
+    height: Float,
+    material: Material,
+    capped: bool,
+}
+
+impl Cylinder {
+    pub fn new(
+        base: Point3<Float>,
+        axis: Vector3<Float>,
+        radius: Float,
+        height: Float,
+        material: Material,
+        capped: bool,
+    ) -> Self {
+        Self {
+            base,
+            axis: axis.normalize(),
+            radius,
+            height,
+            material,
+            capped,
+        }
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, ray: &Ray, t_range: Range<Float>) -> Option<HitRecord<'_>> {
+        let radius2 = self.radius * self.radius;
+        let mut closest: Option<(Float, Vector3<Float>)> = None;
+        let mut consider = |t: Float, outward_normal: Vector3<Float>| {
+            if t_range.contains(&t) && closest.is_none_or(|(closest_t, _)| t < closest_t) {
+                closest = Some((t, outward_normal));
+            }
+        };
+
+        // side: the ray and the offset from the axis, both with the axial part removed
+        let direction = ray.direction - self.axis * ray.direction.dot(self.axis);
+        let offset = (ray.origin - self.base) - self.axis * (ray.origin - self.base).dot(self.axis);
+        for t in solve_quadratic(
+            direction.dot(direction),
+            offset.dot(direction),
+            offset.dot(offset) - radius2,
+        )
+        .iter()
+        .flatten()
+        {
+            let from_base = ray.at(*t) - self.base;
+            let s = from_base.dot(self.axis);
+            if (0.0..=self.height).contains(&s) {
+                consider(*t, (from_base - s * self.axis) / self.radius);
+            }
+        }
+
+        // caps
+        let denominator = ray.direction.dot(self.axis);
+        if self.capped && denominator.abs() > 1e-12 {
+            for (center, outward_normal) in [
+                (self.base, -self.axis),
+                (self.base + self.height * self.axis, self.axis),
+            ] {
+                let t = (center - ray.origin).dot(self.axis) / denominator;
+                let from_center = ray.at(t) - center;
+                if from_center.dot(from_center) <= radius2 {
+                    consider(t, outward_normal);
+                }
+            }
+        }
+
+        closest.map(|(t, outward_normal)| HitRecord::new(ray, t, outward_normal, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let extent = self
+            .axis
+            .map(|a| self.radius * (1.0 - a * a).max(0.0).sqrt() + RECT_THICKNESS);
+        let top = self.base + self.height * self.axis;
+        Some(surrounding_box(
+            &Aabb::new(self.base - extent, self.base + extent),
+            &Aabb::new(top - extent, top + extent),
+        ))
+    }
+}
+
+pub struct Triangle {
+    vertices: [Point3<Float>; 3],
+    material: Material,
+    epsilon: Float,
+}
+
+impl Triangle {
+    pub fn new(
+        v0: Point3<Float>,
+        v1: Point3<Float>,
+        v2: Point3<Float>,
+        material: Material,
+    ) -> Self {
+        Self {
+            vertices: [v0, v1, v2],
+            material,
+            epsilon: DEFAULT_EPSILON,
+        }
+    }
+
+    /// Overrides the near clip of rays spawned from this triangle's surface.
+    pub fn with_epsilon(mut self, epsilon: Float) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+}
+
+impl Hittable for Triangle {
+    /// Möller–Trumbore intersection
+    fn hit(&self, ray: &Ray, t_range: Range<Float>) -> Option<HitRecord<'_>> {
+        let [v0, v1, v2] = self.vertices;
+        let edge1 = v1 - v0;
+        let edge2 = v2 - v0;
+        let p = ray.direction.cross(edge2);
+        let determinant = edge1.dot(p);
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+        let inverse_determinant = 1.0 / determinant;
+        let from_v0 = ray.origin - v0;
+        let u = from_v0.dot(p) * inverse_determinant;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = from_v0.cross(edge1);
+        let v = ray.direction.dot(q) * inverse_determinant;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = edge2.dot(q) * inverse_determinant;
+        if !t_range.contains(&t) {
+            return None;
+        }
+        let outward_normal = edge1.cross(edge2).normalize();
+        Some(HitRecord {
+            u,
+            v,
+            t_min: self.epsilon,
+            ..HitRecord::new(ray, t, outward_normal, &self.material)
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // pad so that axis-aligned triangles don't get a flat box
+        const PADDING: Float = 1e-4;
+        let [v0, v1, v2] = self.vertices;
+        let min = Point3::new(
+            v0.x.min(v1.x).min(v2.x) - PADDING,
+            v0.y.min(v1.y).min(v2.y) - PADDING,
+            v0.z.min(v1.z).min(v2.z) - PADDING,
+        );
+        let max = Point3::new(
+            v0.x.max(v1.x).max(v2.x) + PADDING,
+            v0.y.max(v1.y).max(v2.y) + PADDING,
+            v0.z.max(v1.z).max(v2.z) + PADDING,
+        );
+        Some(Aabb::new(min, max))
+    }
+}
+
+/// Half thickness given to the bounding boxes of flat primitives
+const RECT_THICKNESS: Float = 1e-4;
+
+/// Rectangle on the plane `z = k`, spanning `x0..x1` and `y0..y1`
+pub struct XyRect {
+    x0: Float,
+    x1: Float,
+    y0: Float,
+    y1: Float,
+    k: Float,
+    material: Material,
+}
+
+impl XyRect {
+    pub fn new(x0: Float, x1: Float, y0: Float, y1: Float, k: Float, material: Material) -> Self {
+        Self {
+            x0,
+            x1,
+            y0,
+            y1,
+            k,
+            material,
+        }
+    }
+}
+
+impl Hittable for XyRect {
+    fn hit(&self, ray: &Ray, t_range: Range<Float>) -> Option<HitRecord<'_>> {
+        hit_axis_aligned_rect(
+            ray,
+            t_range,
+            [0, 1, 2],
+            [self.x0, self.x1, self.y0, self.y1, self.k],
+            Vector3::unit_z(),
+            &self.material,
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(
+            Point3::new(self.x0, self.y0, self.k - RECT_THICKNESS),
+            Point3::new(self.x1, self.y1, self.k + RECT_THICKNESS),
+        ))
+    }
+}
+
+/// Rectangle on the plane `y = k`, spanning `x0..x1` and `z0..z1`
+pub struct XzRect {
+    x0: Float,
+    x1: Float,
+    z0: Float,
+    z1: Float,
+    k: Float,
+    material: Material,
+}
+
+impl XzRect {
+    pub fn new(x0: Float, x1: Float, z0: Float, z1: Float, k: Float, material: Material) -> Self {
+        Self {
+            x0,
+            x1,
+            z0,
+            z1,
+            k,
+            material,
+        }
+    }
+}
+
+impl Hittable for XzRect {
+    fn hit(&self, ray: &Ray, t_range: Range<Float>) -> Option<HitRecord<'_>> {
+        hit_axis_aligned_rect(
+            ray,
+            t_range,
+            [0, 2, 1],
+            [self.x0, self.x1, self.z0, self.z1, self.k],
+            Vector3::unit_y(),
+            &self.material,
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(
+            Point3::new(self.x0, self.k - RECT_THICKNESS, self.z0),
+            Point3::new(self.x1, self.k + RECT_THICKNESS, self.z1),
+        ))
+    }
+
+    /// Uniform over the rectangle's area, converted to solid angle
+    fn pdf_value(&self, origin: Point3<Float>, direction: Vector3<Float>) -> Float {
+        match self.hit(
+            &Ray::new(origin, direction),
+            DEFAULT_EPSILON..Float::INFINITY,
+        ) {
+            Some(record) => {
+                let area = (self.x1 - self.x0) * (self.z1 - self.z0);
+                let distance2 = record.t * record.t * direction.magnitude2();
+                let cosine = direction.y.abs() / direction.magnitude();
+                distance2 / (cosine * area)
+            }
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: Point3<Float>, u: Float, v: Float) -> Vector3<Float> {
+        let point = Point3::new(
+            self.x0 + u * (self.x1 - self.x0),
+            self.k,
+            self.z0 + v * (self.z1 - self.z0),
+        );
+        point - origin
+    }
+}
+
+/// Rectangle on the plane `x = k`, spanning `y0..y1` and `z0..z1`
+pub struct YzRect {
+    y0: Float,
+    y1: Float,
+    z0: Float,
+    z1: Float,
+    k: Float,
+    material: Material,
+}
+
+impl YzRect {
+    pub fn new(y0: Float, y1: Float, z0: Float, z1: Float, k: Float, material: Material) -> Self {
+        Self {
+            y0,
+            y1,
+            z0,
+            z1,
+            k,
+            material,
+        }
+    }
+}
+
+impl Hittable for YzRect {
+    fn hit(&self, ray: &Ray, t_range: Range<Float>) -> Option<HitRecord<'_>> {
+        hit_axis_aligned_rect(
+            ray,
+            t_range,
+            [1, 2, 0],
+            [self.y0, self.y1, self.z0, self.z1, self.k],
+            Vector3::unit_x(),
+            &self.material,
+        )
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(
+            Point3::new(self.k - RECT_THICKNESS, self.y0, self.z0),
+            Point3::new(self.k + RECT_THICKNESS, self.y1, self.z1),
+        ))
+    }
+}
+
+/// Infinite plane through `point`, facing `normal`
+pub struct Plane {
+    point: Point3<Float>,
+    normal: Vector3<Float>,
+    material: Material,
+}
+
+impl Plane {
+    pub fn new(point: Point3<Float>, normal: Vector3<Float>, material: Material) -> Self {
+        Self {
+            point,
+            normal: normal.normalize(),
+            material,
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, ray: &Ray, t_range: Range<Float>) -> Option<HitRecord<'_>> {
+        let denominator = ray.direction.dot(self.normal);
+        if denominator.abs() < 1e-12 {
+            return None;
+        }
+        let t = (self.point - ray.origin).dot(self.normal) / denominator;
+        if !t_range.contains(&t) {
+            return None;
+        }
+        Some(HitRecord::new(ray, t, self.normal, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
+}
+
+/// Flat circle of `radius` around `center`, facing `normal`
+pub struct Disk {
+    center: Point3<Float>,
+    normal: Vector3<Float>,
+    radius: Float,
+    material: Material,
+}
+
+impl Disk {
+    pub fn new(
+        center: Point3<Float>,
+        normal: Vector3<Float>,
+        radius: Float,
+        material: Material,
+    ) -> Self {
+        Self {
+            center,
+            normal: normal.normalize(),
+            radius,
+            material,
+        }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, t_range: Range<Float>) -> Option<HitRecord<'_>> {
+        let denominator = ray.direction.dot(self.normal);
+        if denominator.abs() < 1e-12 {
+            return None;
+        }
+        let t = (self.center - ray.origin).dot(self.normal) / denominator;
+        if !t_range.contains(&t) {
+            return None;
+        }
+        let from_center = ray.at(t) - self.center;
+        if from_center.dot(from_center) > self.radius * self.radius {
+            return None;
+        }
+        Some(HitRecord::new(ray, t, self.normal, &self.material))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        // the rim reaches radius * sin(angle between the axis and the normal) along each axis
+        let extent = self
+            .normal
+            .map(|n| self.radius * (1.0 - n * n).max(0.0).sqrt() + RECT_THICKNESS);
+        Some(Aabb::new(self.center - extent, self.center + extent))
+    }
+}
+
+/// Swaps which side of `object` counts as the front face
+pub struct FlipFace<H: Hittable> {
+    object: H,
+}
+
+impl<H: Hittable> FlipFace<H> {
+    pub fn new(object: H) -> Self {
+        Self { object }
+    }
+}
+
+impl<H: Hittable> Hittable for FlipFace<H> {
+    fn hit(&self, ray: &Ray, t_range: Range<Float>) -> Option<HitRecord<'_>> {
+        self.object.hit(ray, t_range).map(|record| HitRecord {
+            front_face: !record.front_face,
+            ..record
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: Point3<Float>, direction: Vector3<Float>) -> Float {
+        self.object.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Point3<Float>, u: Float, v: Float) -> Vector3<Float> {
+        self.object.random(origin, u, v)
+    }
+}
+
+pub struct Translate {
+    object: Arc<dyn Hittable>,
+    offset: Vector3<Float>,
+}
+
+impl Translate {
+    pub fn new(object: Arc<dyn Hittable>, offset: Vector3<Float>) -> Self {
+        Self { object, offset }
+    }
+}
+
+impl Hittable for Translate {
+    fn hit(&self, ray: &Ray, t_range: Range<Float>) -> Option<HitRecord<'_>> {
+        let moved = Ray::new(ray.origin - self.offset, ray.direction).with_time(ray.time);
+        self.object.hit(&moved, t_range).map(|record| HitRecord {
+            p: record.p + self.offset,
+            ..record
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box().map(|bounding_box| {
+            Aabb::new(
+                bounding_box.min + self.offset,
+                bounding_box.max + self.offset,
+            )
+        })
+    }
+}
+
+/// Rotates `object` about the y axis
+pub struct RotateY {
+    object: Arc<dyn Hittable>,
+    sin_theta: Float,
+    cos_theta: Float,
+    bounding_box: Option<Aabb>,
+}
+
+impl RotateY {
+    pub fn new(object: Arc<dyn Hittable>, angle_degrees: Float) -> Self {
+        let (sin_theta, cos_theta) = angle_degrees.to_radians().sin_cos();
+        let bounding_box = object.bounding_box().map(|bounding_box| {
+            let corners = (0..8).map(|corner| {
+                let x = if corner & 1 == 0 {
+                    bounding_box.min.x
+                } else {
+                    bounding_box.max.x
+                };
+                let y = if corner & 2 == 0 {
+                    bounding_box.min.y
+                } else {
+                    bounding_box.max.y
+                };
+                let z = if corner & 4 == 0 {
+                    bounding_box.min.z
+                } else {
+                    bounding_box.max.z
+                };
+                let corner = Point3::new(
+                    cos_theta * x + sin_theta * z,
+                    y,
+                    -sin_theta * x + cos_theta * z,
+                );
+                Aabb::new(corner, corner)
+            });
+            corners
+                .reduce(|a, b| surrounding_box(&a, &b))
+                .expect("A box has corners")
+        });
+        Self {
+            object,
+            sin_theta,
+            cos_theta,
+            bounding_box,
+        }
+    }
+
+    /// World space to object space
+    fn rotate_in(&self, v: Vector3<Float>) -> Vector3<Float> {
+        Vector3::new(
+            self.cos_theta * v.x - self.sin_theta * v.z,
+            v.y,
+            self.sin_theta * v.x + self.cos_theta * v.z,
+        )
+    }
+
+    /// Object space to world space
+    fn rotate_out(&self, v: Vector3<Float>) -> Vector3<Float> {
+        Vector3::new(
+            self.cos_theta * v.x + self.sin_theta * v.z,
+            v.y,
+            -self.sin_theta * v.x + self.cos_theta * v.z,
+        )
+    }
+}
+
+impl Hittable for RotateY {
+    fn hit(&self, ray: &Ray, t_range: Range<Float>) -> Option<HitRecord<'_>> {
+        let origin = Point3::from_vec(self.rotate_in(ray.origin.to_vec()));
+        let rotated = Ray::new(origin, self.rotate_in(ray.direction)).with_time(ray.time);
+        self.object.hit(&rotated, t_range).map(|record| HitRecord {
+            p: Point3::from_vec(self.rotate_out(record.p.to_vec())),
+            normal: self.rotate_out(record.normal),
+            tangent_frame: record
+                .tangent_frame
+                .map(|(tangent, bitangent)| (self.rotate_out(tangent), self.rotate_out(bitangent))),
+            ..record
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bounding_box
+    }
+}
+
+/// Perturbs the shading normals of `object` with a tangent-space normal map: colors remapped
+/// from `0..1` to `-1..1` are normals along the tangent, the bitangent and the surface normal.
+/// Hits without a tangent frame are left as they are.
+pub struct NormalMap {
+    object: Arc<dyn Hittable>,
+    map: Arc<dyn Texture>,
+}
+
+impl NormalMap {
+    pub fn new(object: Arc<dyn Hittable>, map: Arc<dyn Texture>) -> Self {
+        Self { object, map }
+    }
+}
+
+impl Hittable for NormalMap {
+    fn hit(&self, ray: &Ray, t_range: Range<Float>) -> Option<HitRecord<'_>> {
+        self.object
+            .hit(ray, t_range)
+            .map(|record| match record.tangent_frame {
+                Some((tangent, bitangent)) => {
+                    let local = 2.0 * self.map.value(record.u, record.v, record.p)
+                        - Vector3::new(1.0, 1.0, 1.0);
+                    let normal = local.x * tangent + local.y * bitangent + local.z * record.normal;
+                    HitRecord {
+                        normal: normal.normalize(),
+                        ..record
+                    }
+                }
+                None => record,
+            })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box()
+    }
+
+    fn pdf_value(&self, origin: Point3<Float>, direction: Vector3<Float>) -> Float {
+        self.object.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Point3<Float>, u: Float, v: Float) -> Vector3<Float> {
+        self.object.random(origin, u, v)
+    }
+}
+
+/// Axis-aligned box made of six rectangles, with normals pointing out of the box
+pub struct BoxPrim {
+    min: Point3<Float>,
+    max: Point3<Float>,
+    sides: Vec<Arc<dyn Hittable>>,
+}
+
+impl BoxPrim {
+    pub fn new(min: Point3<Float>, max: Point3<Float>, material: Material) -> Self {
+        let sides: Vec<Arc<dyn Hittable>> = vec![
+            Arc::new(XyRect::new(
+                min.x,
+                max.x,
+                min.y,
+                max.y,
+                max.z,
+                material.clone(),
+            )),
+            Arc::new(FlipFace::new(XyRect::new(
+                min.x,
+                max.x,
+                min.y,
+                max.y,
+                min.z,
+                material.clone(),
+            ))),
+            Arc::new(XzRect::new(
+                min.x,
+                max.x,
+                min.z,
+                max.z,
+                max.y,
+                material.clone(),
+            )),
+            Arc::new(FlipFace::new(XzRect::new(
+                min.x,
+                max.x,
+                min.z,
+                max.z,
+                min.y,
+                material.clone(),
+            ))),
+            Arc::new(YzRect::new(
+                min.y,
+                max.y,
+                min.z,
+                max.z,
+                max.x,
+                material.clone(),
+            )),
+            Arc::new(FlipFace::new(YzRect::new(
+                min.y, max.y, min.z, max.z, min.x, material,
+            ))),
+        ];
+        Self { min, max, sides }
+    }
+}
+
+impl Hittable for BoxPrim {
+    fn hit(&self, ray: &Ray, t_range: Range<Float>) -> Option<HitRecord<'_>> {
+        self.sides.hit(ray, t_range)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(self.min, self.max))
+    }
+}
+
+/// `axes` are the two in-plane axes followed by the plane's normal axis, and `bounds` holds
+/// `[a0, a1, b0, b1, k]` along those axes.
+fn hit_axis_aligned_rect<'material>(
+    ray: &Ray,
+    t_range: Range<Float>,
+    axes: [usize; 3],
+    bounds: [Float; 5],
+    outward_normal: Vector3<Float>,
+    material: &'material Material,
+) -> Option<HitRecord<'material>> {
+    let [a_axis, b_axis, k_axis] = axes;
+    let [a0, a1, b0, b1, k] = bounds;
+    let t = (k - ray.origin[k_axis]) / ray.direction[k_axis];
+    if !t_range.contains(&t) {
+        return None;
+    }
+    let a = ray.origin[a_axis] + t * ray.direction[a_axis];
+    let b = ray.origin[b_axis] + t * ray.direction[b_axis];
+    if !(a0..=a1).contains(&a) || !(b0..=b1).contains(&b) {
+        return None;
+    }
+    Some(HitRecord {
+        u: (a - a0) / (a1 - a0),
+        v: (b - b0) / (b1 - b0),
+        ..HitRecord::new(ray, t, outward_normal, material)
+    })
+}
+
+/// Maps a point on the unit sphere to `(u, v)`, with `u` going around the y axis from x = -1
+/// and `v` going from the bottom (y = -1) to the top (y = 1).
+pub fn sphere_uv(p: Vector3<Float>) -> (Float, Float) {
+    let theta = (-p.y).clamp(-1.0, 1.0).acos();
+    let phi = (-p.z).atan2(p.x) + PI;
+    (phi / (2.0 * PI), theta / PI)
+}
+
+/// Tangent along increasing `u` and bitangent along increasing `v` of `sphere_uv` at the point
+/// `p` of the unit sphere. At the poles, where `u` is undefined, any tangent is used.
+pub fn sphere_tangent_frame(p: Vector3<Float>) -> (Vector3<Float>, Vector3<Float>) {
+    let around = Vector3::new(p.z, 0.0, -p.x);
+    let length = around.magnitude();
+    let tangent = if length > 1e-6 {
+        around / length
+    } else {
+        Vector3::new(1.0, 0.0, 0.0)
+    };
+    (tangent, p.cross(tangent).normalize())
+}
+
+/// Roots of `a t^2 + 2 half_b t + c = 0` in ascending order.
+fn solve_quadratic(a: Float, half_b: Float, c: Float) -> [Option<Float>; 2] {
+    let discriminant = half_b * half_b - a * c;
+    if a == 0.0 || discriminant < 0.0 {
+        [None, None]
+    } else {
+        let sqrt_discriminant = discriminant.sqrt();
+        [
+            Some((-half_b - sqrt_discriminant) / a),
+            Some((-half_b + sqrt_discriminant) / a),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{texture::SolidColor, Color};
+
+    use super::*;
+
+    fn gray() -> Material {
+        Material::new_lambertian(Color::new(0.5, 0.5, 0.5))
+    }
+
+    #[test]
+    fn sphere_box_spans_the_radius() {
+        let sphere = Sphere::new(Point3::new(1.0, 2.0, 3.0), 0.5, gray());
+        assert_eq!(
+            sphere.bounding_box(),
+            Some(Aabb::new(
+                Point3::new(0.5, 1.5, 2.5),
+                Point3::new(1.5, 2.5, 3.5)
+            ))
+        );
+    }
+
+    #[test]
+    fn list_box_is_the_union_of_its_objects() {
+        let spheres = vec![
+            Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, gray()),
+            Sphere::new(Point3::new(3.0, 0.0, 0.0), 0.5, gray()),
+        ];
+        assert_eq!(
+            spheres.bounding_box(),
+            Some(Aabb::new(
+                Point3::new(-1.0, -1.0, -1.0),
+                Point3::new(3.5, 1.0, 1.0)
+            ))
+        );
+        assert_eq!(Vec::<Sphere>::new().bounding_box(), None);
+    }
+
+    #[test]
+    fn triangle_centroid_hit() {
+        let triangle = Triangle::new(
+            Point3::new(0.0, 0.0, -2.0),
+            Point3::new(1.0, 0.0, -2.0),
+            Point3::new(0.0, 1.0, -2.0),
+            gray(),
+        );
+        let centroid = Point3::new(1.0 / 3.0, 1.0 / 3.0, -2.0);
+        let ray = Ray::new(Point3::new(1.0 / 3.0, 1.0 / 3.0, 1.0), -Vector3::unit_z());
+        let record = triangle.hit(&ray, 0.001..Float::INFINITY).unwrap();
+        assert!((record.t - 3.0).abs() < 1e-5);
+        assert!((record.p - centroid).magnitude() < 1e-5);
+        assert!((record.u - 1.0 / 3.0).abs() < 1e-5 && (record.v - 1.0 / 3.0).abs() < 1e-5);
+        assert!(record.front_face);
+        assert_eq!(record.normal, Vector3::unit_z());
+
+        assert!(triangle.hit(&ray, 0.001..2.9).is_none());
+        let outside = Ray::new(Point3::new(0.6, 0.6, 1.0), -Vector3::unit_z());
+        assert!(triangle.hit(&outside, 0.001..Float::INFINITY).is_none());
+
+        assert_eq!(record.t_min, DEFAULT_EPSILON);
+        let triangle = triangle.with_epsilon(0.05);
+        assert_eq!(
+            triangle.hit(&ray, 0.001..Float::INFINITY).unwrap().t_min,
+            0.05
+        );
+    }
+
+    #[test]
+    fn perpendicular_ray_hits_xz_rect() {
+        let rect = XzRect::new(-1.0, 1.0, -2.0, 2.0, 3.0, gray());
+        let ray = Ray::new(Point3::new(0.5, 0.0, -1.5), Vector3::unit_y());
+        let record = rect.hit(&ray, 0.001..Float::INFINITY).unwrap();
+        assert!((record.t - 3.0).abs() < 1e-5);
+        assert!((record.p - Point3::new(0.5, 3.0, -1.5)).magnitude() < 1e-5);
+        assert_eq!(record.normal, -Vector3::unit_y());
+        assert!(!record.front_face);
+
+        let outside = Ray::new(Point3::new(1.5, 0.0, 0.0), Vector3::unit_y());
+        assert!(rect.hit(&outside, 0.001..Float::INFINITY).is_none());
+
+        let bounding_box = rect.bounding_box().unwrap();
+        assert!(bounding_box.min.y < 3.0 && bounding_box.max.y > 3.0);
+    }
+
+    #[test]
+    fn ray_through_a_box_reports_the_entry_face() {
+        let solid = BoxPrim::new(
+            Point3::new(-1.0, -1.0, -1.0),
+            Point3::new(1.0, 1.0, 1.0),
+            gray(),
+        );
+        let ray = Ray::new(Point3::new(0.2, 0.3, 5.0), -Vector3::unit_z());
+        let record = solid.hit(&ray, 0.001..Float::INFINITY).unwrap();
+        assert!((record.t - 4.0).abs() < 1e-5);
+        assert_eq!(record.normal, Vector3::unit_z());
+        assert!(record.front_face);
+
+        let exit = solid.hit(&ray, record.t + 0.001..Float::INFINITY).unwrap();
+        assert!((exit.t - 6.0).abs() < 1e-5);
+        assert!(!exit.front_face);
+    }
+
+    #[test]
+    fn translated_hits_are_offset_untranslated_hits() {
+        let offset = Vector3::new(1.0, 2.0, -3.0);
+        let sphere = Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, gray()));
+        let translated = Translate::new(sphere.clone(), offset);
+        let ray = Ray::new(Point3::new(1.3, 2.2, 5.0), -Vector3::unit_z());
+        let record = translated.hit(&ray, 0.001..Float::INFINITY).unwrap();
+        let untranslated = Ray::new(ray.origin - offset, ray.direction);
+        let expected = sphere.hit(&untranslated, 0.001..Float::INFINITY).unwrap();
+        assert!((record.p - (expected.p + offset)).magnitude() < 1e-5);
+        assert_eq!(record.normal, expected.normal);
+        assert_eq!(
+            translated.bounding_box(),
+            Some(Aabb::new(
+                Point3::new(0.0, 1.0, -4.0),
+                Point3::new(2.0, 3.0, -2.0)
+            ))
+        );
+    }
+
+    #[test]
+    fn rotate_y_turns_objects_and_normals() {
+        let sphere = Arc::new(Sphere::new(Point3::new(2.0, 0.0, 0.0), 0.5, gray()));
+        let rotated = RotateY::new(sphere, 90.0);
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), -Vector3::unit_z());
+        let record = rotated.hit(&ray, 0.001..Float::INFINITY).unwrap();
+        assert!((record.p - Point3::new(0.0, 0.0, -1.5)).magnitude() < 1e-5);
+        assert!((record.normal - Vector3::unit_z()).magnitude() < 1e-5);
+        let bounding_box = rotated.bounding_box().unwrap();
+        assert!((bounding_box.min - Point3::new(-0.5, -0.5, -2.5)).magnitude() < 1e-5);
+        assert!((bounding_box.max - Point3::new(0.5, 0.5, -1.5)).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn sphere_uv_of_known_points() {
+        let (_, v) = sphere_uv(Vector3::unit_y());
+        assert!((v - 1.0).abs() < 1e-6);
+        let (u, v) = sphere_uv(Vector3::unit_x());
+        assert!((u - 0.5).abs() < 1e-6 && (v - 0.5).abs() < 1e-6);
+
+        let sphere = Sphere::new(Point3::new(0.0, 3.0, 0.0), 2.0, gray());
+        let ray = Ray::new(Point3::new(5.0, 3.0, 0.0), -Vector3::unit_x());
+        let record = sphere.hit(&ray, 0.001..Float::INFINITY).unwrap();
+        assert!((record.u - 0.5).abs() < 1e-6 && (record.v - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn moving_sphere_is_hit_where_it_is_at_the_ray_time() {
+        let sphere = MovingSphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(4.0, 0.0, 0.0),
+            0.0,
+            1.0,
+            0.5,
+            gray(),
+        );
+        let through_midpoint = Ray::new(Point3::new(2.0, 0.0, 5.0), -Vector3::unit_z());
+        let record = sphere
+            .hit(
+                &through_midpoint.clone().with_time(0.5),
+                0.001..Float::INFINITY,
+            )
+            .unwrap();
+        assert!((record.p - Point3::new(2.0, 0.0, 0.5)).magnitude() < 1e-5);
+        assert!(sphere
+            .hit(&through_midpoint, 0.001..Float::INFINITY)
+            .is_none());
+
+        let bounding_box = sphere.bounding_box().unwrap();
+        assert!((bounding_box.min - Point3::new(-0.5, -0.5, -0.5)).magnitude() < 1e-5);
+        assert!((bounding_box.max - Point3::new(4.5, 0.5, 0.5)).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn disk_is_hit_up_to_its_rim() {
+        let disk = Disk::new(Point3::new(0.0, 1.0, 0.0), Vector3::unit_y(), 2.0, gray());
+        let inside = Ray::new(Point3::new(1.9, 4.0, 0.0), -Vector3::unit_y());
+        let record = disk.hit(&inside, 0.001..Float::INFINITY).unwrap();
+        assert!((record.t - 3.0).abs() < 1e-5);
+        assert_eq!(record.normal, Vector3::unit_y());
+        assert!(record.front_face);
+
+        let just_outside = Ray::new(Point3::new(1.42, 4.0, 1.42), -Vector3::unit_y());
+        assert!(disk.hit(&just_outside, 0.001..Float::INFINITY).is_none());
+        let edge_on = Ray::new(Point3::new(-5.0, 1.0, 0.0), Vector3::unit_x());
+        assert!(disk.hit(&edge_on, 0.001..Float::INFINITY).is_none());
+    }
+
+    #[test]
+    fn plane_is_hit_at_its_distance_and_missed_going_away() {
+        let plane = Plane::new(Point3::new(0.0, -1.0, 0.0), Vector3::unit_y(), gray());
+        let down = Ray::new(Point3::new(100.0, 3.0, -50.0), Vector3::new(0.0, -2.0, 0.0));
+        let record = plane.hit(&down, 0.001..Float::INFINITY).unwrap();
+        assert!((record.t - 2.0).abs() < 1e-5);
+        assert!((record.p - Point3::new(100.0, -1.0, -50.0)).magnitude() < 1e-5);
+        assert!(record.front_face);
+
+        let up = Ray::new(Point3::new(0.0, 3.0, 0.0), Vector3::new(0.3, 1.0, 0.0));
+        assert!(plane.hit(&up, 0.001..Float::INFINITY).is_none());
+        assert_eq!(plane.bounding_box(), None);
+    }
+
+    #[test]
+    fn cylinder_side_caps_and_open_top() {
+        let cylinder = |capped| {
+            Cylinder::new(
+                Point3::new(0.0, 0.0, 0.0),
+                Vector3::unit_y(),
+                1.0,
+                2.0,
+                gray(),
+                capped,
+            )
+        };
+        let capped = cylinder(true);
+        let side = Ray::new(Point3::new(5.0, 1.0, 0.0), -Vector3::unit_x());
+        let record = capped.hit(&side, 0.001..Float::INFINITY).unwrap();
+        assert!((record.t - 4.0).abs() < 1e-5);
+        assert!((record.normal - Vector3::unit_x()).magnitude() < 1e-5);
+
+        let down = Ray::new(Point3::new(0.5, 5.0, 0.0), -Vector3::unit_y());
+        let record = capped.hit(&down, 0.001..Float::INFINITY).unwrap();
+        assert!((record.t - 3.0).abs() < 1e-5);
+        assert_eq!(record.normal, Vector3::unit_y());
+
+        // without caps a ray through the top only meets the inside of the wall
+        let open = cylinder(false);
+        assert!(open.hit(&down, 0.001..Float::INFINITY).is_none());
+        let slanted = Ray::new(Point3::new(0.0, 5.0, 0.0), Vector3::new(0.25, -1.0, 0.0));
+        let record = open.hit(&slanted, 0.001..Float::INFINITY).unwrap();
+        assert!((record.p - Point3::new(1.0, 1.0, 0.0)).magnitude() < 1e-5);
+        assert!(!record.front_face);
+    }
+
+    #[test]
+    fn list_of_overlapping_spheres_returns_the_nearer_hit() {
+        let mut list = HittableList::new();
+        assert!(list.is_empty());
+        list.add(Sphere::new(Point3::new(0.0, 0.0, -3.0), 1.0, gray()));
+        list.add(Sphere::new(Point3::new(0.0, 0.0, -2.5), 1.0, gray()));
+        assert_eq!(list.len(), 2);
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), -Vector3::unit_z());
+        let record = list.hit(&ray, 0.001..Float::INFINITY).unwrap();
+        assert!((record.t - 1.5).abs() < 1e-5);
+
+        list.clear();
+        assert!(list.hit(&ray, 0.001..Float::INFINITY).is_none());
+        assert_eq!(list.bounding_box(), None);
+    }
+
+    #[test]
+    fn spheres_round_trip_through_json() {
+        for sphere in [
+            Sphere::new(Point3::new(1.0, 2.0, 3.0), 0.5, gray()),
+            Sphere::new_inverted(
+                Point3::new(0.0, 0.0, 0.0),
+                3.0,
+                Material::new_dielectric(1.5),
+            ),
+        ] {
+            let json = serde_json::to_string(&sphere).unwrap();
+            assert_eq!(serde_json::from_str::<Sphere>(&json).unwrap(), sphere);
+        }
+        let minimal = r#"{"center": [1, 2, 3], "radius": 0.5, "material": {"type": "lambertian", "albedo": [0.5, 0.5, 0.5]}}"#;
+        assert_eq!(
+            serde_json::from_str::<Sphere>(minimal).unwrap(),
+            Sphere::new(Point3::new(1.0, 2.0, 3.0), 0.5, gray())
+        );
+    }
+
+    #[test]
+    fn flat_normal_map_keeps_the_normal_even_at_the_poles() {
+        let sphere = Arc::new(Sphere::new(Point3::new(0.0, 0.0, 0.0), 2.0, gray()));
+        let flat = Arc::new(SolidColor::new(Color::new(0.5, 0.5, 1.0)));
+        let mapped = NormalMap::new(sphere.clone(), flat);
+        for &direction in &[
+            Vector3::new(0.0, 0.0, -1.0),
+            Vector3::new(0.0, -1.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(-1.0, -2.0, 0.5),
+        ] {
+            let ray = Ray::new(Point3::new(0.0, 0.0, 0.0) - 5.0 * direction, direction);
+            let expected = sphere.hit(&ray, 0.001..Float::INFINITY).unwrap();
+            let (tangent, bitangent) = expected.tangent_frame.unwrap();
+            assert!(
+                tangent.dot(bitangent).abs() < 1e-6 && tangent.dot(expected.normal).abs() < 1e-6
+            );
+            let record = mapped.hit(&ray, 0.001..Float::INFINITY).unwrap();
+            assert!(
+                (record.normal - expected.normal).magnitude() < 1e-6,
+                "{:?}",
+                direction
+            );
+        }
+    }
+
+    #[test]
+    fn option_hits_only_when_some() {
+        let sphere = Sphere::new(Point3::new(0.0, 0.0, -2.0), 0.5, gray());
+        let rays = [
+            Ray::new(Point3::new(0.0, 0.0, 0.0), -Vector3::unit_z()),
+            Ray::new(Point3::new(0.3, 0.2, 0.0), -Vector3::unit_z()),
+            Ray::new(Point3::new(0.6, 0.0, 0.0), -Vector3::unit_z()),
+        ];
+        let some = Some(sphere.clone());
+        for ray in &rays {
+            assert!(None::<Sphere>.hit(ray, 0.001..Float::INFINITY).is_none());
+            let expected = sphere.hit(ray, 0.001..Float::INFINITY);
+            let hit = some.hit(ray, 0.001..Float::INFINITY);
+            assert_eq!(
+                hit.map(|hit| (hit.p, hit.t)),
+                expected.map(|hit| (hit.p, hit.t))
+            );
+        }
+        assert!(None::<Sphere>.bounding_box().is_none());
+        assert_eq!(some.bounding_box(), sphere.bounding_box());
+    }
+
+    #[test]
+    fn inverted_sphere_faces_inward_from_inside() {
+        let center = Point3::new(1.0, 0.0, 0.0);
+        let ray = Ray::new(center, Vector3::unit_x());
+        let outward = Sphere::new(center, 2.0, gray());
+        let inverted = Sphere::new_inverted(center, 2.0, gray());
+
+        let hit = inverted.hit(&ray, 0.001..Float::INFINITY).unwrap();
+        assert_eq!(hit.p, Point3::new(3.0, 0.0, 0.0));
+        assert_eq!(hit.normal, -Vector3::unit_x());
+        assert!(hit.front_face);
+
+        let hit = outward.hit(&ray, 0.001..Float::INFINITY).unwrap();
+        assert_eq!(hit.normal, -Vector3::unit_x());
+        assert!(!hit.front_face);
+    }
+
+    #[test]
+    fn capsule_body_and_cap_hits() {
+        let capsule = Capsule::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 2.0, 0.0),
+            0.5,
+            gray(),
+        );
+        let hit = |origin: Point3<Float>, direction: Vector3<Float>| {
+            capsule
+                .hit(&Ray::new(origin, direction), 0.001..Float::INFINITY)
+                .map(|record| (record.p, record.normal))
+        };
+
+        // the body, along its side
+        let (p, normal) = hit(Point3::new(5.0, 1.0, 0.0), -Vector3::unit_x()).unwrap();
+        assert!((p - Point3::new(0.5, 1.0, 0.0)).magnitude() < 1e-6);
+        assert!((normal - Vector3::unit_x()).magnitude() < 1e-6);
+
+        // the caps, along the axis and at an angle
+        let (p, normal) = hit(Point3::new(0.0, 5.0, 0.0), -Vector3::unit_y()).unwrap();
+        assert!((p - Point3::new(0.0, 2.5, 0.0)).magnitude() < 1e-6);
+        assert!((normal - Vector3::unit_y()).magnitude() < 1e-6);
+        let (p, normal) = hit(Point3::new(0.3, -5.0, 0.0), Vector3::unit_y()).unwrap();
+        let expected = Point3::new(0.3, -0.4, 0.0);
+        assert!((p - expected).magnitude() < 1e-6);
+        assert!((normal - (expected - Point3::new(0.0, 0.0, 0.0)) / 0.5).magnitude() < 1e-6);
+
+        // above the body only the rounded cap is left
+        let (p, _) = hit(Point3::new(5.0, 2.45, 0.0), -Vector3::unit_x()).unwrap();
+        assert!((p.x - (0.25 - 0.2025 as Float).sqrt()).abs() < 1e-5);
+        assert!(hit(Point3::new(5.0, 2.45, 0.3), -Vector3::unit_x()).is_none());
+        assert!(hit(Point3::new(5.0, 3.0, 0.0), -Vector3::unit_x()).is_none());
+    }
+
+    #[test]
+    fn capsule_with_equal_ends_is_a_sphere() {
+        let center = Point3::new(1.0, 2.0, 3.0);
+        let capsule = Capsule::new(center, center, 0.5, gray());
+        let sphere = Sphere::new(center, 0.5, gray());
+        for direction in [
+            Vector3::new(-1.0, 0.0, 0.0),
+            Vector3::new(-1.0, -0.1, 0.05),
+            Vector3::new(-1.0, 0.3, -0.2),
+        ] {
+            let ray = Ray::new(Point3::new(5.0, 2.0, 3.0), direction);
+            let expected = sphere.hit(&ray, 0.001..Float::INFINITY);
+            let record = capsule.hit(&ray, 0.001..Float::INFINITY);
+            assert_eq!(record.is_some(), expected.is_some());
+            if let (Some(record), Some(expected)) = (record, expected) {
+                assert!((record.t - expected.t).abs() < 1e-5);
+                assert!((record.normal - expected.normal).magnitude() < 1e-6);
+            }
+        }
+        assert_eq!(capsule.bounding_box(), sphere.bounding_box());
+    }
+
+    #[test]
+    fn mixed_sphere_and_plane_return_the_nearer_hit() {
+        let sphere = Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, gray());
+        let plane = Plane::new(Point3::new(0.0, 0.0, 0.0), Vector3::unit_y(), gray());
+        let mixed: Vec<Arc<dyn Hittable>> = vec![Arc::new(plane), Arc::new(sphere)];
+        let mut list = HittableList::new();
+        list.add(Plane::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::unit_y(),
+            gray(),
+        ));
+        list.add(Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, gray()));
+
+        let hit = |ray: &Ray| {
+            let from_vec = mixed.hit(ray, 0.001..Float::INFINITY).map(|hit| hit.p);
+            let from_list = list.hit(ray, 0.001..Float::INFINITY).map(|hit| hit.p);
+            assert_eq!(from_vec, from_list);
+            from_vec
+        };
+        // straight down: the top of the sphere comes before the plane
+        let down = Ray::new(Point3::new(0.0, 5.0, 0.0), -Vector3::unit_y());
+        assert!((hit(&down).unwrap() - Point3::new(0.0, 2.0, 0.0)).magnitude() < 1e-6);
+        // beside the sphere only the plane is hit
+        let beside = Ray::new(Point3::new(3.0, 5.0, 0.0), -Vector3::unit_y());
+        assert!((hit(&beside).unwrap() - Point3::new(3.0, 0.0, 0.0)).magnitude() < 1e-6);
+        // looking up from below the plane passes the sphere after the plane
+        let up = Ray::new(Point3::new(0.0, -1.0, 0.0), Vector3::unit_y());
+        assert!((hit(&up).unwrap() - Point3::new(0.0, 0.0, 0.0)).magnitude() < 1e-6);
+        let away = Ray::new(Point3::new(3.0, 5.0, 0.0), Vector3::unit_y());
+        assert!(hit(&away).is_none());
+    }
+
+    #[test]
+    fn list_samples_each_light_equally_often() {
+        let light = |x: Float| {
+            XzRect::new(
+                x - 0.5,
+                x + 0.5,
+                -0.5,
+                0.5,
+                2.0,
+                Material::new_diffuse_light(Color::new(4.0, 4.0, 4.0)),
+            )
+        };
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let mut lights = HittableList::new();
+        assert_eq!(lights.pdf_value(origin, Vector3::unit_y()), 0.0);
+        lights.add(light(-2.0));
+        lights.add(light(2.0));
+
+        // a direction towards one light has half that light's density
+        let toward_left = Vector3::new(-2.0, 2.0, 0.0);
+        let left_pdf = light(-2.0).pdf_value(origin, toward_left);
+        assert!(left_pdf > 0.0);
+        assert!((lights.pdf_value(origin, toward_left) - left_pdf / 2.0).abs() < 1e-9);
+
+        let mut left = 0;
+        for i in 0..100 {
+            let u = (i as Float + 0.5) / 100.0;
+            let direction = lights.random(origin, u, 0.5);
+            assert!(lights.pdf_value(origin, direction) > 0.0);
+            if direction.x < 0.0 {
+                left += 1;
+            }
+        }
+        assert_eq!(left, 50);
+        // each half of `u` spans the whole light it picks
+        let first = lights.random(origin, 0.0, 0.0);
+        let last = lights.random(origin, 1.0 - Float::EPSILON, 0.0);
+        assert!((first.x - -2.5).abs() < 1e-6 && (last.x - 2.5).abs() < 1e-4);
+    }
 }
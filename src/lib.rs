@@ -0,0 +1,65 @@
+use cgmath::{Point3, Vector3};
+
+pub mod aabb;
+pub mod background;
+pub mod bvh;
+pub mod camera;
+pub mod description;
+pub mod hittable;
+pub mod lut;
+pub mod material;
+pub mod medium;
+pub mod mesh;
+pub mod output;
+pub mod perlin;
+pub mod postprocess;
+pub mod progress;
+pub mod random;
+pub mod render;
+pub mod scene;
+pub mod spectrum;
+pub mod texture;
+pub mod tile;
+
+#[derive(Clone)]
+pub struct Ray {
+    pub origin: Point3<Float>,
+    pub direction: Vector3<Float>,
+    /// Moment within the shutter interval the ray samples, for moving objects
+    pub time: Float,
+}
+
+impl Ray {
+    pub fn new(origin: Point3<Float>, direction: Vector3<Float>) -> Self {
+        Self {
+            origin,
+            direction,
+            time: 0.0,
+        }
+    }
+
+    pub fn with_time(mut self, time: Float) -> Self {
+        self.time = time;
+        self
+    }
+
+    pub fn at(&self, t: Float) -> Point3<Float> {
+        self.origin + t * self.direction
+    }
+}
+
+pub type Color = Vector3<Float>;
+
+/// Floating-point type of all geometry and colors. It is `f64` unless the `f32` feature is
+/// enabled: single precision renders faster, but the limited precision of hit points shows up
+/// sooner as shadow acne and speckles on large or far away geometry, and in subtle banding.
+#[cfg(not(feature = "f32"))]
+pub type Float = f64;
+#[cfg(feature = "f32")]
+pub type Float = f32;
+
+#[cfg(feature = "f32")]
+pub use std::f32::consts;
+/// Mathematical constants in `Float` precision
+#[cfg(not(feature = "f32"))]
+pub use std::f64::consts;
@@ -0,0 +1,192 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use cgmath::ElementWise;
+
+use crate::{Color, Float};
+
+/// A 3D color lookup table as stored in Adobe/Resolve `.cube` files.
+pub struct Lut3d {
+    size: usize,
+    domain_min: Color,
+    domain_max: Color,
+    // red varies fastest, then green, then blue
+    table: Vec<Color>,
+}
+
+impl Lut3d {
+    pub fn from_cube<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::parse_cube(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse_cube(source: &str) -> io::Result<Self> {
+        let mut size = None;
+        let mut domain_min = Color::new(0.0, 0.0, 0.0);
+        let mut domain_max = Color::new(1.0, 1.0, 1.0);
+        let mut table = Vec::new();
+        for line in source.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("TITLE") {
+                continue;
+            }
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("LUT_3D_SIZE") => {
+                    let value = tokens
+                        .next()
+                        .ok_or_else(|| invalid("missing LUT_3D_SIZE"))?;
+                    size = Some(
+                        value
+                            .parse::<usize>()
+                            .map_err(|_| invalid("invalid LUT_3D_SIZE"))?,
+                    );
+                }
+                Some("DOMAIN_MIN") => domain_min = parse_color(tokens)?,
+                Some("DOMAIN_MAX") => domain_max = parse_color(tokens)?,
+                Some("LUT_1D_SIZE") => return Err(invalid("1D LUTs are not supported")),
+                Some(first) => {
+                    table.push(parse_color(std::iter::once(first).chain(tokens))?);
+                }
+                None => {}
+            }
+        }
+        let size = size.ok_or_else(|| invalid("missing LUT_3D_SIZE"))?;
+        if size < 2 {
+            return Err(invalid("LUT_3D_SIZE must be at least 2"));
+        }
+        let extent = domain_max - domain_min;
+        if extent.x <= 0.0 || extent.y <= 0.0 || extent.z <= 0.0 {
+            return Err(invalid("DOMAIN_MAX must be greater than DOMAIN_MIN"));
+        }
+        if table.len() != size * size * size {
+            return Err(invalid(
+                "number of table entries does not match LUT_3D_SIZE",
+            ));
+        }
+        Ok(Self {
+            size,
+            domain_min,
+            domain_max,
+            table,
+        })
+    }
+
+    /// Looks `color` up with trilinear interpolation, clamping it to the LUT domain.
+    pub fn apply(&self, color: Color) -> Color {
+        let max_index = (self.size - 1) as Float;
+        let normalized =
+            (color - self.domain_min).div_element_wise(self.domain_max - self.domain_min);
+        let position = [normalized.x, normalized.y, normalized.z]
+            .map(|channel| channel.clamp(0.0, 1.0) * max_index);
+        let lower = position.map(|channel| (channel.floor() as usize).min(self.size - 2));
+        let fraction = [0, 1, 2].map(|axis| position[axis] - lower[axis] as Float);
+
+        let mut result = Color::new(0.0, 0.0, 0.0);
+        for corner in 0..8 {
+            let offset = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+            let weight = (0..3)
+                .map(|axis| {
+                    if offset[axis] == 1 {
+                        fraction[axis]
+                    } else {
+                        1.0 - fraction[axis]
+                    }
+                })
+                .product::<Float>();
+            let r = lower[0] + offset[0];
+            let g = lower[1] + offset[1];
+            let b = lower[2] + offset[2];
+            result += weight * self.table[(b * self.size + g) * self.size + r];
+        }
+        result
+    }
+
+    pub fn apply_to(&self, pixels: &mut [Color]) {
+        pixels
+            .iter_mut()
+            .for_each(|pixel| *pixel = self.apply(*pixel));
+    }
+}
+
+fn parse_color<'a, I: Iterator<Item = &'a str>>(mut tokens: I) -> io::Result<Color> {
+    let mut channel = || -> io::Result<Float> {
+        tokens
+            .next()
+            .ok_or_else(|| invalid("expected three color components"))?
+            .parse()
+            .map_err(|_| invalid("invalid color component"))
+    };
+    Ok(Color::new(channel()?, channel()?, channel()?))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::InnerSpace;
+
+    use super::*;
+
+    /// A 2x2x2 cube file mapping every corner of the unit cube through `f`
+    fn cube_source(header: &str, f: impl Fn(Color) -> Color) -> String {
+        let mut source = format!("TITLE \"test\"\n{}\nLUT_3D_SIZE 2\n", header);
+        for b in 0..2 {
+            for g in 0..2 {
+                for r in 0..2 {
+                    let color = f(Color::new(r as Float, g as Float, b as Float));
+                    source += &format!("{} {} {}\n", color.x, color.y, color.z);
+                }
+            }
+        }
+        source
+    }
+
+    fn pixels() -> Vec<Color> {
+        vec![
+            Color::new(0.0, 0.0, 0.0),
+            Color::new(1.0, 1.0, 1.0),
+            Color::new(0.25, 0.5, 0.75),
+            Color::new(0.9, 0.1, 0.6),
+        ]
+    }
+
+    #[test]
+    fn identity_lut_leaves_pixels_unchanged() {
+        let lut = Lut3d::parse_cube(&cube_source("", |color| color)).unwrap();
+        let mut graded = pixels();
+        lut.apply_to(&mut graded);
+        for (graded, pixel) in graded.iter().zip(pixels()) {
+            assert!((graded - pixel).magnitude() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn invert_lut_inverts_pixels() {
+        let white = Color::new(1.0, 1.0, 1.0);
+        let lut = Lut3d::parse_cube(&cube_source("# invert", |color| white - color)).unwrap();
+        let mut graded = pixels();
+        lut.apply_to(&mut graded);
+        for (graded, pixel) in graded.iter().zip(pixels()) {
+            assert!((graded - (white - pixel)).magnitude() < 1e-6);
+        }
+        // out-of-domain colors are clamped first
+        assert!(
+            (lut.apply(Color::new(2.0, -1.0, 0.5)) - Color::new(0.0, 1.0, 0.5)).magnitude() < 1e-6
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_cubes() {
+        let degenerate = cube_source("DOMAIN_MIN 0 0 0\nDOMAIN_MAX 1 0 1", |color| color);
+        assert!(Lut3d::parse_cube(&degenerate).is_err());
+        let mut short = cube_source("", |color| color);
+        short.truncate(short.trim_end().rfind('\n').unwrap());
+        assert!(Lut3d::parse_cube(&short).is_err());
+        assert!(Lut3d::parse_cube("LUT_1D_SIZE 2\n0 0 0\n1 1 1").is_err());
+    }
+}
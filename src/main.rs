@@ -1,195 +1,419 @@
-use std::{collections::VecDeque, io::Write};
-
-use cgmath::{ElementWise, InnerSpace, Point3, Vector3};
-use rand::{distributions::Uniform, prelude::Distribution, rngs::SmallRng, Rng, SeedableRng};
-use rayon::iter::{IntoParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
-
-mod camera;
-mod hittable;
-mod material;
-use crate::{
-    camera::Camera,
-    hittable::{Hittable, Sphere},
-    material::Material,
+use std::io::{self, BufWriter, Write};
+
+use ray_tracing::{
+    lut::Lut3d,
+    output::{save_png, write_color, write_ppm_binary, write_ppm_header, write_rgb8, ToneMap},
+    postprocess::auto_exposure,
+    scene::{load_json, Scene},
+    Float,
 };
 
-#[derive(Clone)]
-pub struct Ray {
-    pub origin: Point3<f64>,
-    pub direction: Vector3<f64>,
+/// Command-line options; `None` keeps the scene's own value
+#[derive(Default)]
+struct Args {
+    scene: Option<String>,
+    width: Option<usize>,
+    height: Option<usize>,
+    samples: Option<usize>,
+    max_depth: Option<usize>,
+    seed: Option<u64>,
+    checkpoint: Option<String>,
+    wavefront: bool,
+    preview: Option<String>,
+    /// Writes each row as soon as it is rendered instead of keeping the whole image
+    stream: bool,
+    /// Mean luminance the final image is exposed to
+    auto_exposure: Option<Float>,
+    /// `.cube` file grading the tone-mapped image
+    lut: Option<String>,
+    gamma: Option<Float>,
+    tone_map: Option<ToneMap>,
+    /// Plain-text P3 instead of binary P6
+    ascii: bool,
+    /// Saves a PNG there instead of printing a PPM
+    png: Option<String>,
 }
 
-impl Ray {
-    pub fn new(origin: Point3<f64>, direction: Vector3<f64>) -> Self {
-        Self { origin, direction }
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Args, String> {
+    let mut parsed = Args::default();
+    while let Some(arg) = args.next() {
+        let target = match arg.as_str() {
+            "--width" => &mut parsed.width,
+            "--height" => &mut parsed.height,
+            "--samples" => &mut parsed.samples,
+            "--max-depth" => &mut parsed.max_depth,
+            "--seed" => {
+                let value = option_value(&mut args, &arg)?;
+                parsed.seed = Some(value.parse().map_err(|_| {
+                    format!("{} expects an unsigned integer, got {:?}", arg, value)
+                })?);
+                continue;
+            }
+            "--checkpoint" => {
+                parsed.checkpoint = Some(option_value(&mut args, &arg)?);
+                continue;
+            }
+            "--wavefront" => {
+                parsed.wavefront = true;
+                continue;
+            }
+            "--preview" => {
+                parsed.preview = Some(option_value(&mut args, &arg)?);
+                continue;
+            }
+            "--stream" => {
+                parsed.stream = true;
+                continue;
+            }
+            "--lut" => {
+                parsed.lut = Some(option_value(&mut args, &arg)?);
+                continue;
+            }
+            "--auto-exposure" => {
+                parsed.auto_exposure = Some(positive_number(&mut args, &arg)?);
+                continue;
+            }
+            "--gamma" => {
+                parsed.gamma = Some(positive_number(&mut args, &arg)?);
+                continue;
+            }
+            "--tone-map" => {
+                let value = option_value(&mut args, &arg)?;
+                parsed.tone_map = Some(match value.as_str() {
+                    "none" => ToneMap::None,
+                    "reinhard" => ToneMap::Reinhard,
+                    "aces" => ToneMap::AcesFilmic,
+                    _ => {
+                        return Err(format!(
+                            "{} expects none, reinhard or aces, got {:?}",
+                            arg, value
+                        ))
+                    }
+                });
+                continue;
+            }
+            "--ascii" => {
+                parsed.ascii = true;
+                continue;
+            }
+            "--png" => {
+                parsed.png = Some(option_value(&mut args, &arg)?);
+                continue;
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+            _ if parsed.scene.is_none() => {
+                parsed.scene = Some(arg);
+                continue;
+            }
+            _ => return Err(format!("unexpected argument {}", arg)),
+        };
+        let value = option_value(&mut args, &arg)?;
+        *target = Some(
+            value
+                .parse()
+                .ok()
+                .filter(|value| *value > 0)
+                .ok_or_else(|| format!("{} expects a positive integer, got {:?}", arg, value))?,
+        );
     }
-
-    pub fn at(&self, t: f64) -> Point3<f64> {
-        self.origin + t * self.direction
+    let modes = [
+        parsed.checkpoint.is_some(),
+        parsed.wavefront,
+        parsed.preview.is_some(),
+        parsed.stream,
+    ];
+    if modes.iter().filter(|&&mode| mode).count() > 1 {
+        return Err(
+            "only one of --checkpoint, --wavefront, --preview and --stream can be given"
+                .to_string(),
+        );
+    }
+    if parsed.stream
+        && (parsed.auto_exposure.is_some() || parsed.lut.is_some() || parsed.png.is_some())
+    {
+        return Err(
+            "--auto-exposure, --lut and --png need the whole image, not --stream".to_string(),
+        );
+    }
+    if parsed.ascii && parsed.png.is_some() {
+        return Err("only one of --ascii and --png can be given".to_string());
     }
+    Ok(parsed)
 }
 
-pub type Color = Vector3<f64>;
-
-fn ray_color<H: Hittable, R: Rng>(ray: &Ray, hittable: &H, depth: usize, rng: &mut R) -> Color {
-    let mut ray = ray.clone();
-    let mut depth = depth;
-    let mut stack = VecDeque::new();
-    loop {
-        if depth == 0 {
-            stack.push_back(Color::new(0.0, 0.0, 0.0));
-            break;
-        }
-
-        let record = hittable.hit(&ray, 0.001..);
-        if let Some(record) = record {
-            if let Some((scattered, attenuation)) = record.material.scatter(&ray, &record, rng) {
-                stack.push_back(attenuation);
-                ray = scattered;
-                depth -= 1;
-            } else {
-                stack.push_back(Color::new(0.0, 0.0, 0.0));
-                break;
-            }
-        } else {
-            let unit_direction = ray.direction.normalize();
-            let t = (unit_direction.y + 1.0) / 2.0;
-            stack.push_back((1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0));
-            break;
-        }
-    }
-    stack.into_par_iter().reduce(
-        || Color::new(1.0, 1.0, 1.0),
-        |left, right| left.mul_element_wise(right),
-    )
+/// The positive, finite number following `option`
+fn positive_number<I: Iterator<Item = String>>(
+    args: &mut I,
+    option: &str,
+) -> Result<Float, String> {
+    let value = option_value(args, option)?;
+    value
+        .parse()
+        .ok()
+        .filter(|number: &Float| *number > 0.0 && number.is_finite())
+        .ok_or_else(|| format!("{} expects a positive number, got {:?}", option, value))
 }
 
-fn write_color<W: Write>(mut writer: W, color: Color, samples_per_pixel: usize) {
-    // with gamma-correction for gamma = 2.0
-    let r = (color.x / samples_per_pixel as f64).sqrt();
-    let g = (color.y / samples_per_pixel as f64).sqrt();
-    let b = (color.z / samples_per_pixel as f64).sqrt();
-    writeln!(
-        writer,
-        "{} {} {}",
-        (256.0 * r.max(0.0).min(0.999)) as i32,
-        (256.0 * g.max(0.0).min(0.999)) as i32,
-        (256.0 * b.max(0.0).min(0.999)) as i32,
-    )
-    .expect("Couldn't write a color");
+/// The argument following `option`
+fn option_value<I: Iterator<Item = String>>(args: &mut I, option: &str) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("{} needs a value", option))
 }
 
 fn main() {
-    const ASPECT_RATIO: f64 = 3.0 / 2.0;
-    const IMAGE_WIDTH: usize = 1200;
-    const IMAGE_HEIGHT: usize = (IMAGE_WIDTH as f64 / ASPECT_RATIO) as usize;
-    const SAMPLES_PER_PIXEL: usize = 500;
-    const MAX_DEPTH: usize = 50;
-
-    let ground_material = Material::new_lambertian(Color::new(0.5, 0.5, 0.5));
-    let mut hittables = Vec::new();
-    hittables.push(Sphere::new(
-        Point3::new(0.0, -1000.0, 0.0),
-        1000.0,
-        ground_material,
-    ));
-
-    let mut rng = SmallRng::seed_from_u64(
-        0b0101010101010101_0101010101010101_0101010101010101_0101010101010101,
-    );
-    let distribution = Uniform::from(0.0..1.0);
-    let dielectric = Material::new_dielectric(1.5);
-    for a in -11..11 {
-        for b in -11..11 {
-            let material_probability = distribution.sample(&mut rng);
-            let center = Point3::new(
-                a as f64 + 0.9 * distribution.sample(&mut rng),
-                0.2,
-                b as f64 + 0.9 * distribution.sample(&mut rng),
-            );
-
-            if (center - Point3::new(4.0, 0.2, 0.0))
-                .dot(center - Point3::new(4.0, 0.2, 0.0))
-                .sqrt()
-                > 0.9
-            {
-                let material = if material_probability < 0.8 {
-                    let albedo = Color::new(
-                        distribution.sample(&mut rng),
-                        distribution.sample(&mut rng),
-                        distribution.sample(&mut rng),
-                    )
-                    .mul_element_wise(Color::new(
-                        distribution.sample(&mut rng),
-                        distribution.sample(&mut rng),
-                        distribution.sample(&mut rng),
-                    ));
-                    Material::new_lambertian(albedo)
-                } else if material_probability < 0.95 {
-                    let distribution = Uniform::from(0.5..1.0);
-                    let albedo = Color::new(
-                        distribution.sample(&mut rng),
-                        distribution.sample(&mut rng),
-                        distribution.sample(&mut rng),
-                    );
-                    let distribution = Uniform::from(0.0..0.5);
-                    let fuzz = distribution.sample(&mut rng);
-                    Material::new_metal(albedo, fuzz)
-                } else {
-                    dielectric.clone()
-                };
-                hittables.push(Sphere::new(center, 0.2, material));
-            }
+    /// Side of the tiles saved to the checkpoint given with `--checkpoint`
+    const TILE_SIZE: usize = 32;
+    /// Samples per pixel between updates of the image given with `--preview`
+    const PREVIEW_BATCH: usize = 16;
+
+    let args = parse_args(std::env::args().skip(1)).unwrap_or_else(|message| {
+        eprintln!("error: {}", message);
+        eprintln!(
+            "usage: ray-tracing [SCENE | FILE.json] [--width N] [--height N] [--samples N] [--max-depth N] \
+             [--seed N] [--checkpoint PATH | --wavefront | --preview PNG | --stream] [--auto-exposure KEY] [--lut CUBE] \
+             [--gamma G] [--tone-map none|reinhard|aces] [--ascii | --png PATH]"
+        );
+        std::process::exit(2);
+    });
+    let mut scene = match args.scene.as_deref() {
+        None | Some("random_spheres") => Scene::random_spheres(),
+        Some("cornell_box") => Scene::cornell_box(),
+        Some(path) if path.ends_with(".json") => load_json(path).unwrap_or_else(|error| {
+            eprintln!("error: {}: {}", path, error);
+            std::process::exit(1);
+        }),
+        Some(name) => {
+            eprintln!("Unknown scene: {}", name);
+            std::process::exit(1);
+        }
+    };
+    let lut = args.lut.as_ref().map(|path| {
+        Lut3d::from_cube(path).unwrap_or_else(|error| {
+            eprintln!("error: {}: {}", path, error);
+            std::process::exit(1);
+        })
+    });
+    let settings = &mut scene.settings;
+    // a single dimension keeps the scene's aspect ratio, both refit the camera to them
+    match (args.width, args.height) {
+        (Some(width), Some(height)) => {
+            scene.camera = scene
+                .camera
+                .clone()
+                .with_aspect_ratio(width as Float / height as Float);
+            settings.width = width;
+            settings.height = height;
+        }
+        (Some(width), None) => {
+            settings.height = (width * settings.height / settings.width).max(1);
+            settings.width = width;
+        }
+        (None, Some(height)) => {
+            settings.width = (height * settings.width / settings.height).max(1);
+            settings.height = height;
         }
+        (None, None) => {}
+    }
+    if let Some(samples) = args.samples {
+        settings.samples_per_pixel = samples;
+    }
+    if let Some(max_depth) = args.max_depth {
+        settings.max_depth = max_depth;
+    }
+    if let Some(seed) = args.seed {
+        settings.seed = seed;
     }
-    hittables.push(Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, dielectric));
-    hittables.push(Sphere::new(
-        Point3::new(-4.0, 1.0, 0.0),
-        1.0,
-        Material::new_lambertian(Color::new(0.4, 0.2, 0.1)),
-    ));
-    hittables.push(Sphere::new(
-        Point3::new(4.0, 1.0, 0.0),
-        1.0,
-        Material::new_metal(Color::new(0.7, 0.6, 0.5), 0.0),
-    ));
-
-    let camera_position = Point3::new(13.0, 3.0, 2.0);
-    let camera_look_at = Point3::new(0.0, 0.0, 0.0);
-    let up = Vector3::new(0.0, 1.0, 0.0);
-    let aperture = 0.1;
-    let camera = Camera::new(
-        camera_position,
-        camera_look_at,
-        up,
-        20.0,
-        ASPECT_RATIO,
-        aperture,
-        10.0,
-    );
-
-    // Print in PPM Image format
-    println!("P3");
-    println!("{} {}", IMAGE_WIDTH, IMAGE_HEIGHT);
-    println!("255"); // max color
-    let mut rngs: Vec<_> = (0..SAMPLES_PER_PIXEL)
-        .map(|_| SmallRng::from_entropy())
-        .collect();
-    for y in 0..IMAGE_HEIGHT {
-        eprintln!("Scan lines remaining: {}", IMAGE_HEIGHT - y);
-        for x in 0..IMAGE_WIDTH {
-            let color = rngs
-                .par_iter_mut()
-                .map(|rng| {
-                    let u = (x as f64 + distribution.sample(rng)) / (IMAGE_WIDTH as f64 - 1.0);
-                    let v = ((IMAGE_HEIGHT - y) as f64 + distribution.sample(rng))
-                        / (IMAGE_HEIGHT as f64 - 1.0);
-                    let ray = camera.ray(u, v, rng);
-                    ray_color(&ray, &hittables, MAX_DEPTH, rng)
+
+    let gamma = args.gamma.unwrap_or(2.0);
+    let mut tone_map = args.tone_map.unwrap_or(ToneMap::None);
+
+    if args.stream {
+        let settings = &scene.settings;
+        let samples_per_pixel = settings.samples_per_pixel;
+        let mut writer = BufWriter::new(io::stdout().lock());
+        let written = if args.ascii {
+            writeln!(writer, "P3\n{} {}\n255", settings.width, settings.height).and_then(|()| {
+                scene.render_rows(|row| {
+                    for &color in row {
+                        write_color(&mut writer, color, samples_per_pixel, gamma, tone_map);
+                    }
+                    Ok(())
+                })
+            })
+        } else {
+            write_ppm_header(&mut writer, settings.width, settings.height).and_then(|()| {
+                scene.render_rows(|row| {
+                    write_rgb8(&mut writer, row, samples_per_pixel, gamma, tone_map)
                 })
-                .sum();
-            write_color(std::io::stdout(), color, SAMPLES_PER_PIXEL);
+            })
+        };
+        written
+            .and_then(|()| writer.flush())
+            .expect("Couldn't write the image");
+        eprintln!("Done");
+        return;
+    }
+
+    let mut pixels = if let Some(path) = &args.checkpoint {
+        scene.render_tiled(TILE_SIZE, path).unwrap_or_else(|error| {
+            eprintln!("error: checkpoint {}: {}", path, error);
+            std::process::exit(1);
+        })
+    } else if args.wavefront {
+        scene.render_wavefront()
+    } else if let Some(path) = &args.preview {
+        let (width, height) = (scene.settings.width, scene.settings.height);
+        scene.render_progressive(PREVIEW_BATCH, |average, _| {
+            if let Err(error) = save_png(path, average, width, height, 1, gamma, tone_map) {
+                eprintln!("error: preview {}: {}", path, error);
+            }
+        })
+    } else {
+        scene.render()
+    };
+    let settings = &scene.settings;
+    let mut samples_per_pixel = settings.samples_per_pixel;
+    if let Some(key) = args.auto_exposure {
+        // exposure is measured on averaged radiance, not on sums of samples
+        pixels
+            .iter_mut()
+            .for_each(|pixel| *pixel /= samples_per_pixel as Float);
+        samples_per_pixel = 1;
+        auto_exposure(&mut pixels, key);
+    }
+    if let Some(lut) = &lut {
+        // the LUT grades the tone-mapped image, so tone mapping moves before it
+        pixels
+            .iter_mut()
+            .for_each(|pixel| *pixel = tone_map.apply(*pixel / samples_per_pixel as Float));
+        samples_per_pixel = 1;
+        tone_map = ToneMap::None;
+        lut.apply_to(&mut pixels);
+    }
+
+    if let Some(path) = &args.png {
+        save_png(
+            path,
+            &pixels,
+            settings.width,
+            settings.height,
+            samples_per_pixel,
+            gamma,
+            tone_map,
+        )
+        .expect("Couldn't save the image");
+    } else if args.ascii {
+        // Print in PPM Image format
+        println!("P3");
+        println!("{} {}", settings.width, settings.height);
+        println!("255"); // max color
+        for color in pixels {
+            write_color(std::io::stdout(), color, samples_per_pixel, gamma, tone_map);
         }
+    } else {
+        write_ppm_binary(
+            BufWriter::new(io::stdout().lock()),
+            &pixels,
+            settings.width,
+            settings.height,
+            samples_per_pixel,
+            gamma,
+            tone_map,
+        )
+        .expect("Couldn't write the image");
     }
     eprintln!("Done");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parses_the_scene_and_positive_sizes() {
+        let args = parse(&["cornell_box", "--width", "320", "--samples", "8"]).unwrap();
+        assert_eq!(args.scene.as_deref(), Some("cornell_box"));
+        assert_eq!(args.width, Some(320));
+        assert_eq!(args.height, None);
+        assert_eq!(args.samples, Some(8));
+        assert_eq!(args.max_depth, None);
+    }
+
+    #[test]
+    fn rejects_bad_values_and_unknown_options() {
+        assert!(parse(&["--width"]).is_err());
+        assert!(parse(&["--width", "0"]).is_err());
+        assert!(parse(&["--height", "-3"]).is_err());
+        assert!(parse(&["--max-depth", "ten"]).is_err());
+        assert!(parse(&["--fast"]).is_err());
+        assert!(parse(&["a.json", "b.json"]).is_err());
+    }
+
+    #[test]
+    fn parses_the_seed_as_any_unsigned_integer() {
+        assert_eq!(parse(&["--seed", "0"]).unwrap().seed, Some(0));
+        let args = parse(&["--seed", "18446744073709551615", "--width", "8"]).unwrap();
+        assert_eq!(args.seed, Some(u64::MAX));
+        assert_eq!(args.width, Some(8));
+        assert!(parse(&["--seed", "-1"]).is_err());
+        assert!(parse(&["--seed"]).is_err());
+    }
+
+    #[test]
+    fn parses_a_positive_auto_exposure_key() {
+        assert_eq!(parse(&[]).unwrap().auto_exposure, None);
+        let args = parse(&["--auto-exposure", "0.18"]).unwrap();
+        assert_eq!(args.auto_exposure, Some(0.18));
+        assert!(parse(&["--auto-exposure", "0"]).is_err());
+        assert!(parse(&["--auto-exposure", "bright"]).is_err());
+        assert!(parse(&["--auto-exposure"]).is_err());
+    }
+
+    #[test]
+    fn parses_a_lut_path() {
+        assert_eq!(parse(&[]).unwrap().lut, None);
+        let args = parse(&["--lut", "looks/warm.cube"]).unwrap();
+        assert_eq!(args.lut.as_deref(), Some("looks/warm.cube"));
+        assert!(parse(&["--lut"]).is_err());
+    }
+
+    #[test]
+    fn stream_excludes_the_other_modes_and_whole_image_passes() {
+        assert!(parse(&["--stream"]).unwrap().stream);
+        assert!(parse(&["--stream", "--wavefront"]).is_err());
+        assert!(parse(&["--preview", "a.png", "--stream"]).is_err());
+        assert!(parse(&["--stream", "--auto-exposure", "0.18"]).is_err());
+        assert!(parse(&["--lut", "a.cube", "--stream"]).is_err());
+    }
+
+    #[test]
+    fn parses_the_output_options() {
+        let args = parse(&[]).unwrap();
+        assert_eq!(args.gamma, None);
+        assert!(args.tone_map.is_none() && !args.ascii && args.png.is_none());
+
+        let args = parse(&["--gamma", "2.2", "--tone-map", "aces", "--png", "out.png"]).unwrap();
+        assert_eq!(args.gamma, Some(2.2));
+        assert!(matches!(args.tone_map, Some(ToneMap::AcesFilmic)));
+        assert_eq!(args.png.as_deref(), Some("out.png"));
+        let args = parse(&["--tone-map", "reinhard", "--ascii"]).unwrap();
+        assert!(matches!(args.tone_map, Some(ToneMap::Reinhard)));
+        assert!(args.ascii);
+        assert!(matches!(
+            parse(&["--tone-map", "none"]).unwrap().tone_map,
+            Some(ToneMap::None)
+        ));
+
+        assert!(parse(&["--gamma", "0"]).is_err());
+        assert!(parse(&["--gamma", "inf"]).is_err());
+        assert!(parse(&["--tone-map", "filmic"]).is_err());
+        assert!(parse(&["--png"]).is_err());
+        assert!(parse(&["--ascii", "--png", "out.png"]).is_err());
+        assert!(parse(&["--stream", "--png", "out.png"]).is_err());
+        assert!(parse(&["--stream", "--ascii"]).unwrap().stream);
+    }
+}
@@ -1,57 +1,222 @@
+use std::{convert::TryFrom, fmt, sync::Arc};
+
 use cgmath::{AbsDiffEq, InnerSpace, Vector3, Zero};
 use rand::{distributions::Uniform, prelude::Distribution, Rng};
+use serde::{ser::Error, Deserialize, Serialize, Serializer};
+
+use crate::{
+    description::MaterialDescription,
+    hittable::HitRecord,
+    random::{ball_point_from_samples, cosine_direction_from_samples, unit_vector_from_samples},
+    spectrum::Spectrum,
+    texture::{SolidColor, Texture},
+    Color, Float, Ray,
+};
+
+/// How a lambertian surface picks its scatter direction
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LambertianMode {
+    /// The normal plus a random unit vector
+    UnitVector,
+    /// Cosine-weighted sampling of the hemisphere in a frame around the normal; follows the
+    /// BRDF times cosine exactly, which keeps variance low
+    Cosine,
+}
+
+/// Mirrors `v` about the surface with normal `n`.
+pub fn reflect(v: Vector3<Float>, n: Vector3<Float>) -> Vector3<Float> {
+    v - 2.0 * v.dot(n) * n
+}
 
-use crate::{hittable::HitRecord, Color, Ray};
+/// Bends the unit vector `uv` through the surface with unit normal `n` by Snell's law, where
+/// `etai_over_etat` is the ratio of the refractive indices on the incoming and outgoing sides.
+/// Callers check for total internal reflection first.
+pub fn refract(uv: Vector3<Float>, n: Vector3<Float>, etai_over_etat: Float) -> Vector3<Float> {
+    let cos = (-uv.dot(n)).clamp(-1.0, 1.0);
+    let perp = etai_over_etat * (uv + cos * n);
+    let parallel = -((1.0 - perp.dot(perp)).abs().sqrt()) * n;
+    perp + parallel
+}
+
+/// Schlick's approximation of the Fresnel reflectance at an angle of incidence with cosine `cos`.
+pub fn schlick_reflectance(cos: Float, ref_idx: Float) -> Float {
+    let r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
+    let r0 = r0 * r0;
+    r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+}
 
-#[derive(Clone)]
+/// Serialized as its `MaterialDescription`
+#[derive(Clone, Deserialize)]
+#[serde(from = "MaterialDescription")]
 pub enum Material {
-    Lambertian { albedo: Color },
-    Metal { albedo: Color, fuzz: f64 },
-    Dielectric { index_of_refraction: f64 },
+    Lambertian {
+        albedo: Arc<dyn Texture>,
+        mode: LambertianMode,
+    },
+    Metal {
+        albedo: Color,
+        /// Roughness read as the mean of the texture's channels at each hit
+        fuzz: Arc<dyn Texture>,
+    },
+    Dielectric {
+        index_of_refraction: Float,
+        tint_internal_reflections: bool,
+        /// Beer–Lambert absorption coefficient per unit of distance travelled inside
+        absorption: Color,
+    },
+    Transparent,
+    DiffuseLight {
+        emit: Color,
+    },
+    /// Phase function of participating media: scatters uniformly in all directions
+    Isotropic {
+        albedo: Color,
+    },
 }
 
 impl Material {
     pub fn new_lambertian(albedo: Color) -> Self {
-        Self::Lambertian { albedo }
+        Self::new_textured_lambertian(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn new_textured_lambertian(albedo: Arc<dyn Texture>) -> Self {
+        Self::Lambertian {
+            albedo,
+            mode: LambertianMode::UnitVector,
+        }
     }
 
-    pub fn new_metal(albedo: Color, fuzz: f64) -> Self {
+    /// Sets how a lambertian material samples directions; other materials are left as is.
+    pub fn with_lambertian_mode(mut self, lambertian_mode: LambertianMode) -> Self {
+        if let Self::Lambertian { mode, .. } = &mut self {
+            *mode = lambertian_mode;
+        }
+        self
+    }
+
+    pub fn new_metal(albedo: Color, fuzz: Float) -> Self {
+        Self::new_textured_metal(
+            albedo,
+            Arc::new(SolidColor::new(Color::new(fuzz, fuzz, fuzz))),
+        )
+    }
+
+    /// Creates a metal whose roughness varies over the surface, e.g. for scratches.
+    pub fn new_textured_metal(albedo: Color, fuzz: Arc<dyn Texture>) -> Self {
         Self::Metal { albedo, fuzz }
     }
 
-    pub fn new_dielectric(index_of_refraction: f64) -> Self {
+    pub fn new_dielectric(index_of_refraction: Float) -> Self {
+        Self::Dielectric {
+            index_of_refraction,
+            tint_internal_reflections: true,
+            absorption: Color::zero(),
+        }
+    }
+
+    /// Creates a stylized "clean" glass: paths reflected inside it reach the background
+    /// as neutral white instead of picking up the sky color.
+    pub fn new_clean_dielectric(index_of_refraction: Float) -> Self {
+        Self::Dielectric {
+            index_of_refraction,
+            tint_internal_reflections: false,
+            absorption: Color::zero(),
+        }
+    }
+
+    /// Creates a colored glass that absorbs `absorption` per unit of distance inside it, so thick
+    /// parts look darker than thin ones.
+    pub fn new_absorbing_dielectric(index_of_refraction: Float, absorption: Color) -> Self {
         Self::Dielectric {
             index_of_refraction,
+            tint_internal_reflections: true,
+            absorption,
         }
     }
 
-    pub fn scatter<R: Rng>(
+    /// Creates a material that lets rays pass straight through, e.g. for openings.
+    pub fn new_transparent() -> Self {
+        Self::Transparent
+    }
+
+    pub fn new_diffuse_light(emit: Color) -> Self {
+        Self::DiffuseLight { emit }
+    }
+
+    pub fn new_isotropic(albedo: Color) -> Self {
+        Self::Isotropic { albedo }
+    }
+
+    pub fn scatter<S: Spectrum, R: Rng>(
         &self,
         ray: &Ray,
         record: &HitRecord,
         rng: &mut R,
-    ) -> Option<(Ray, Color)> {
+    ) -> Option<(Ray, S)> {
+        let distribution = Uniform::from(0.0..1.0);
+        let u = distribution.sample(rng);
+        let v = distribution.sample(rng);
+        // only metal uses a third sample
+        let w = match self {
+            Self::Metal { .. } => distribution.sample(rng),
+            _ => 0.0,
+        };
+        self.scatter_with(ray, record, u, v, w)
+    }
+
+    /// Scatters `ray` using the explicit uniform samples `u`, `v` and `w` in `[0, 1)`
+    /// instead of drawing them from a RNG, so the same samples always give the same result.
+    /// `w` only sets how deep inside the unit ball the fuzz offset of metal lies.
+    pub fn scatter_with<S: Spectrum>(
+        &self,
+        ray: &Ray,
+        record: &HitRecord,
+        u: Float,
+        v: Float,
+        w: Float,
+    ) -> Option<(Ray, S)> {
         match self {
-            Self::Lambertian { albedo } => {
-                let direction = record.normal + random_unit_vector(rng);
-                let direction = if direction.abs_diff_eq(&Vector3::zero(), f64::EPSILON) {
-                    record.normal
-                } else {
-                    direction
+            Self::Lambertian { albedo, mode } => {
+                let direction = match mode {
+                    LambertianMode::UnitVector => {
+                        let direction = record.normal + unit_vector_from_samples(u, v);
+                        if direction.abs_diff_eq(&Vector3::zero(), Float::EPSILON) {
+                            record.normal
+                        } else {
+                            direction
+                        }
+                    }
+                    LambertianMode::Cosine => {
+                        let local = cosine_direction_from_samples(u, v);
+                        let w = record.normal;
+                        let a = if w.x.abs() > 0.9 {
+                            Vector3::new(0.0, 1.0, 0.0)
+                        } else {
+                            Vector3::new(1.0, 0.0, 0.0)
+                        };
+                        let bitangent = w.cross(a).normalize();
+                        let tangent = w.cross(bitangent);
+                        local.x * tangent + local.y * bitangent + local.z * w
+                    }
                 };
-                Some((Ray::new(record.p, direction), *albedo))
+                Some((
+                    Ray::new(record.p, direction).with_time(ray.time),
+                    S::from_rgb(albedo.value(record.u, record.v, record.p)),
+                ))
             }
             Self::Metal { albedo, fuzz } => {
-                let normalized_ray_direction = ray.direction.normalize();
-                let reflected = normalized_ray_direction
-                    - 2.0 * normalized_ray_direction.dot(record.normal) * record.normal;
+                let fuzz = fuzz.value(record.u, record.v, record.p);
+                let fuzz = (fuzz.x + fuzz.y + fuzz.z) / 3.0;
+                let reflected = reflect(ray.direction.normalize(), record.normal);
                 if reflected.dot(record.normal) > 0.0 {
                     Some((
                         Ray::new(
                             record.p,
-                            reflected + *fuzz * random_vector_in_unit_sphere(rng),
-                        ),
-                        *albedo,
+                            reflected + fuzz * ball_point_from_samples(u, v, w),
+                        )
+                        .with_time(ray.time),
+                        S::from_rgb(*albedo),
                     ))
                 } else {
                     None
@@ -59,52 +224,385 @@ impl Material {
             }
             Self::Dielectric {
                 index_of_refraction,
+                absorption,
+                ..
             } => {
                 let refraction_ratio = if record.front_face {
                     1.0 / *index_of_refraction
                 } else {
                     *index_of_refraction
                 };
+                // `record.normal` faces the incoming ray on either face, so `cos` and `sin` are
+                // those of the angle of incidence whether the ray enters or leaves
                 let direction = ray.direction.normalize();
-                let cos = (-direction.dot(record.normal)).min(1.0).max(-1.0);
-                let sin = (1.0 - cos * cos).sqrt();
-                let reflectance = {
-                    let r0 = (1.0 - refraction_ratio) / (1.0 + refraction_ratio);
-                    let r0 = r0 * r0;
-                    r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+                let cos = (-direction.dot(record.normal)).clamp(-1.0, 1.0);
+                let sin = (1.0 - cos * cos).max(0.0).sqrt();
+                // Snell's law has no solution past the critical angle, so all light is reflected
+                let total_internal_reflection = refraction_ratio * sin > 1.0;
+                // otherwise the Fresnel reflectance is the probability of reflecting
+                let reflects =
+                    total_internal_reflection || schlick_reflectance(cos, refraction_ratio) > u;
+                let direction = if reflects {
+                    reflect(direction, record.normal)
+                } else {
+                    refract(direction, record.normal, refraction_ratio)
                 };
-                let direction = if refraction_ratio * sin > 1.0 || reflectance > random_double(rng)
-                {
-                    let normalized_ray_direction = ray.direction.normalize();
-                    normalized_ray_direction
-                        - 2.0 * normalized_ray_direction.dot(record.normal) * record.normal
+                // Hitting a back face means the incoming segment ran through the interior
+                let attenuation = if record.front_face {
+                    Color::new(1.0, 1.0, 1.0)
                 } else {
-                    let perp = refraction_ratio * (direction + cos * record.normal);
-                    let parallel = -((1.0 - perp.dot(perp)).abs().sqrt()) * record.normal;
-                    perp + parallel
+                    let distance = record.t * ray.direction.magnitude();
+                    absorption.map(|a| (-a * distance).exp())
                 };
-                Some((Ray::new(record.p, direction), Color::new(1.0, 1.0, 1.0)))
+                Some((
+                    Ray::new(record.p, direction).with_time(ray.time),
+                    S::from_rgb(attenuation),
+                ))
+            }
+            Self::Transparent => Some((
+                Ray::new(record.p, ray.direction).with_time(ray.time),
+                S::from_rgb(Color::new(1.0, 1.0, 1.0)),
+            )),
+            Self::DiffuseLight { .. } => None,
+            Self::Isotropic { albedo } => Some((
+                Ray::new(record.p, unit_vector_from_samples(u, v)).with_time(ray.time),
+                S::from_rgb(*albedo),
+            )),
+        }
+    }
+
+    /// Albedo of a perfectly diffuse surface at `record`, for sampling the lights directly;
+    /// `None` for other materials.
+    pub fn diffuse_albedo(&self, record: &HitRecord) -> Option<Color> {
+        match self {
+            Self::Lambertian { albedo, .. } => Some(albedo.value(record.u, record.v, record.p)),
+            _ => None,
+        }
+    }
+
+    /// Base color of the surface at `record`, ignoring how it scatters.
+    pub fn albedo(&self, record: &HitRecord) -> Color {
+        match self {
+            Self::Lambertian { albedo, .. } => albedo.value(record.u, record.v, record.p),
+            Self::Metal { albedo, .. } | Self::Isotropic { albedo } => *albedo,
+            Self::Dielectric { .. } | Self::Transparent => Color::new(1.0, 1.0, 1.0),
+            Self::DiffuseLight { emit } => *emit,
+        }
+    }
+
+    /// Radiance emitted by the surface; black for everything but lights.
+    pub fn emitted(&self) -> Color {
+        match self {
+            Self::DiffuseLight { emit } => *emit,
+            _ => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Whether `scattered`, leaving `record`, is an internal reflection that should not pick
+    /// up the background color.
+    pub fn hides_background(&self, record: &HitRecord, scattered: &Ray) -> bool {
+        match self {
+            Self::Dielectric {
+                tint_internal_reflections: false,
+                ..
+            } => !record.front_face && scattered.direction.dot(record.normal) > 0.0,
+            _ => false,
+        }
+    }
+}
+
+/// Compares variants field by field. Textures are equal when they are the same texture, e.g.
+/// in a clone, or solid colors of the same color.
+impl PartialEq for Material {
+    fn eq(&self, other: &Self) -> bool {
+        fn same_texture(texture: &Arc<dyn Texture>, other: &Arc<dyn Texture>) -> bool {
+            Arc::ptr_eq(texture, other)
+                || matches!(
+                    (texture.solid_color(), other.solid_color()),
+                    (Some(color), Some(other)) if color == other
+                )
+        }
+
+        match (self, other) {
+            (
+                Self::Lambertian { albedo, mode },
+                Self::Lambertian {
+                    albedo: other_albedo,
+                    mode: other_mode,
+                },
+            ) => same_texture(albedo, other_albedo) && mode == other_mode,
+            (
+                Self::Metal { albedo, fuzz },
+                Self::Metal {
+                    albedo: other_albedo,
+                    fuzz: other_fuzz,
+                },
+            ) => albedo == other_albedo && same_texture(fuzz, other_fuzz),
+            (
+                Self::Dielectric {
+                    index_of_refraction,
+                    tint_internal_reflections,
+                    absorption,
+                },
+                Self::Dielectric {
+                    index_of_refraction: other_index_of_refraction,
+                    tint_internal_reflections: other_tint_internal_reflections,
+                    absorption: other_absorption,
+                },
+            ) => {
+                index_of_refraction == other_index_of_refraction
+                    && tint_internal_reflections == other_tint_internal_reflections
+                    && absorption == other_absorption
+            }
+            (Self::Transparent, Self::Transparent) => true,
+            (Self::DiffuseLight { emit }, Self::DiffuseLight { emit: other_emit }) => {
+                emit == other_emit
             }
+            (
+                Self::Isotropic { albedo },
+                Self::Isotropic {
+                    albedo: other_albedo,
+                },
+            ) => albedo == other_albedo,
+            _ => false,
         }
     }
 }
 
-fn random_vector_in_unit_sphere<R: Rng>(rng: &mut R) -> Vector3<f64> {
-    let distribution = Uniform::from(0.0..1.0);
-    loop {
-        let x = distribution.sample(rng);
-        let y = distribution.sample(rng);
-        let z = distribution.sample(rng);
-        if x * x + y * y + z * z <= 1.0 {
-            return Vector3::new(x, y, z);
+/// Shows the serialized form where there is one
+impl fmt::Debug for Material {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match MaterialDescription::try_from(self) {
+            Ok(description) => description.fmt(f),
+            Err(_) => f.debug_struct("Material").finish_non_exhaustive(),
         }
     }
 }
 
-fn random_unit_vector<R: Rng>(rng: &mut R) -> Vector3<f64> {
-    random_vector_in_unit_sphere(rng).normalize()
+impl Serialize for Material {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MaterialDescription::try_from(self)
+            .map_err(S::Error::custom)?
+            .serialize(serializer)
+    }
 }
 
-fn random_double<R: Rng>(rng: &mut R) -> f64 {
-    Uniform::from(0.0..1.0).sample(rng)
+#[cfg(test)]
+mod tests {
+    use cgmath::Point3;
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use super::*;
+    use crate::texture::CheckerTexture;
+
+    fn hit_from_above(material: &Material) -> HitRecord<'_> {
+        let ray = Ray::new(Point3::new(0.0, 1.0, 0.0), -Vector3::unit_y());
+        HitRecord::new(&ray, 1.0, Vector3::unit_y(), material)
+    }
+
+    #[test]
+    fn lights_emit_and_do_not_scatter() {
+        let emit = Color::new(1.0, 2.0, 3.0);
+        let light = Material::new_diffuse_light(emit);
+        let ray = Ray::new(Point3::new(0.0, 1.0, 0.0), -Vector3::unit_y());
+        let mut rng = SmallRng::seed_from_u64(0);
+        assert_eq!(light.emitted(), emit);
+        assert!(light
+            .scatter::<Color, _>(&ray, &hit_from_above(&light), &mut rng)
+            .is_none());
+        assert_eq!(
+            Material::new_lambertian(emit).emitted(),
+            Color::new(0.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn textured_lambertian_attenuates_by_the_texture_at_the_hit() {
+        let checker =
+            CheckerTexture::from_colors(Color::new(1.0, 0.0, 0.0), Color::new(0.0, 0.0, 1.0), 1.0);
+        let material = Material::new_textured_lambertian(Arc::new(checker));
+        let ray = Ray::new(Point3::new(-1.0, 2.0, 1.0), -Vector3::unit_y());
+        let record = HitRecord::new(&ray, 1.0, Vector3::unit_y(), &material);
+        let mut rng = SmallRng::seed_from_u64(0);
+        let (_, attenuation) = material
+            .scatter::<Color, _>(&ray, &record, &mut rng)
+            .unwrap();
+        assert_eq!(attenuation, Color::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn roughness_texture_of_zero_mirrors_and_near_one_spreads() {
+        // smooth where x < 0, rough where x > 0
+        let roughness = CheckerTexture::from_colors(
+            Color::new(0.0, 0.0, 0.0),
+            Color::new(0.95, 0.95, 0.95),
+            1.0,
+        );
+        let material = Material::new_textured_metal(Color::new(0.9, 0.9, 0.9), Arc::new(roughness));
+        let incoming = Vector3::new(1.0, -1.0, 0.0).normalize();
+        let mirrored = Vector3::new(1.0, 1.0, 0.0).normalize();
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut smallest_cosine = |x: Float| {
+            let ray = Ray::new(Point3::new(x - 1.0, 2.0, 1.0), incoming);
+            let record =
+                HitRecord::new(&ray, 2.0_f64.sqrt() as Float, Vector3::unit_y(), &material);
+            (0..1000)
+                .filter_map(|_| material.scatter::<Color, _>(&ray, &record, &mut rng))
+                .map(|(scattered, _)| scattered.direction.normalize().dot(mirrored))
+                .fold(1.0, Float::min)
+        };
+        assert!(smallest_cosine(-1.0) > 1.0 - 1e-6);
+        assert!(smallest_cosine(1.0) < 0.5);
+    }
+
+    #[test]
+    fn cosine_lambertian_never_scatters_below_the_surface() {
+        let material = Material::new_lambertian(Color::new(0.5, 0.5, 0.5))
+            .with_lambertian_mode(LambertianMode::Cosine);
+        let mut rng = SmallRng::seed_from_u64(0);
+        for normal in [
+            Vector3::unit_y(),
+            Vector3::unit_x(),
+            -Vector3::unit_x(),
+            Vector3::new(1.0, -2.0, 0.5).normalize(),
+        ] {
+            let ray = Ray::new(Point3::new(0.0, 0.0, 0.0) + normal, -normal);
+            let record = HitRecord::new(&ray, 1.0, normal, &material);
+            for _ in 0..1000 {
+                let (scattered, _) = material
+                    .scatter::<Color, _>(&ray, &record, &mut rng)
+                    .unwrap();
+                assert!(scattered.direction.dot(normal) >= 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn reflects_at_45_degrees_and_refracts_by_snells_law() {
+        let normal = Vector3::unit_y();
+        let incoming = Vector3::new(1.0, -1.0, 0.0).normalize();
+        let reflected = reflect(incoming, normal);
+        assert!((reflected - Vector3::new(1.0, 1.0, 0.0).normalize()).magnitude() < 1e-6);
+
+        let ratio = 1.0 / 1.5;
+        let refracted = refract(incoming, normal, ratio);
+        assert!((refracted.magnitude() - 1.0).abs() < 1e-6);
+        // sin(outgoing) = ratio sin(incoming), measured from the normal
+        let sin_in = incoming.x;
+        assert!((refracted.x - ratio * sin_in).abs() < 1e-6);
+        assert!(refracted.y < 0.0);
+        assert_eq!(refract(-normal, normal, ratio), -normal);
+    }
+
+    #[test]
+    fn total_internal_reflection_always_reflects() {
+        let glass = Material::new_dielectric(1.5);
+        // leaving the glass 60 degrees off the normal, past the critical angle of about 42
+        let direction = Vector3::new(0.866, 0.5, 0.0);
+        let ray = Ray::new(Point3::new(-0.866, -0.5, 0.0), direction);
+        let record = HitRecord::new(&ray, 1.0, Vector3::unit_y(), &glass);
+        assert!(!record.front_face);
+        for i in 0..100 {
+            let u = i as Float / 100.0;
+            let (scattered, _) = glass
+                .scatter_with::<Color>(&ray, &record, u, 0.5, 0.5)
+                .unwrap();
+            assert!(
+                scattered.direction.y < 0.0,
+                "u {}: {:?}",
+                u,
+                scattered.direction
+            );
+        }
+    }
+
+    #[test]
+    fn materials_round_trip_through_json() {
+        for material in [
+            Material::new_lambertian(Color::new(0.1, 0.2, 0.3)),
+            Material::new_lambertian(Color::new(0.5, 0.5, 0.5))
+                .with_lambertian_mode(LambertianMode::Cosine),
+            Material::new_metal(Color::new(0.7, 0.6, 0.5), 0.25),
+            Material::new_dielectric(1.5),
+            Material::new_clean_dielectric(1.33),
+            Material::new_absorbing_dielectric(1.5, Color::new(0.1, 0.5, 0.5)),
+            Material::new_transparent(),
+            Material::new_diffuse_light(Color::new(4.0, 4.0, 4.0)),
+            Material::new_isotropic(Color::new(0.9, 0.9, 0.9)),
+        ] {
+            let json = serde_json::to_string(&material).unwrap();
+            assert_eq!(serde_json::from_str::<Material>(&json).unwrap(), material);
+        }
+        assert_ne!(Material::new_dielectric(1.5), Material::new_dielectric(1.6));
+
+        let checker =
+            CheckerTexture::from_colors(Color::new(1.0, 0.0, 0.0), Color::new(0.0, 0.0, 1.0), 1.0);
+        let textured = Material::new_textured_lambertian(Arc::new(checker));
+        assert!(serde_json::to_string(&textured).is_err());
+        assert_eq!(textured, textured);
+        assert_eq!(textured, textured.clone());
+    }
+
+    #[test]
+    fn same_samples_give_the_same_scattered_ray() {
+        let materials = [
+            Material::new_lambertian(Color::new(0.5, 0.5, 0.5)),
+            Material::new_lambertian(Color::new(0.5, 0.5, 0.5))
+                .with_lambertian_mode(LambertianMode::Cosine),
+            Material::new_metal(Color::new(0.8, 0.8, 0.8), 0.5),
+            Material::new_dielectric(1.5),
+            Material::new_isotropic(Color::new(0.9, 0.9, 0.9)),
+        ];
+        for material in &materials {
+            let ray = Ray::new(Point3::new(0.3, 1.0, 0.0), Vector3::new(-0.3, -1.0, 0.0));
+            let record = HitRecord::new(&ray, 1.0, Vector3::unit_y(), material);
+            let scatter = |u, v, w| {
+                material
+                    .scatter_with::<Color>(&ray, &record, u, v, w)
+                    .map(|(scattered, attenuation)| (scattered.direction, attenuation))
+            };
+            for &(u, v, w) in &[(0.0, 0.0, 0.0), (0.25, 0.7, 0.1), (0.99, 0.5, 0.9)] {
+                assert_eq!(scatter(u, v, w), scatter(u, v, w));
+            }
+        }
+    }
+
+    #[test]
+    fn textured_materials_equal_their_clones_only() {
+        let checker = || -> Arc<dyn Texture> {
+            Arc::new(CheckerTexture::from_colors(
+                Color::new(0.0, 0.0, 0.0),
+                Color::new(1.0, 1.0, 1.0),
+                10.0,
+            ))
+        };
+        let textured = Material::new_textured_lambertian(checker());
+        assert_eq!(textured.clone(), textured);
+        assert_ne!(Material::new_textured_lambertian(checker()), textured);
+        let rough = Material::new_textured_metal(Color::new(0.8, 0.8, 0.8), checker());
+        assert_eq!(rough.clone(), rough);
+        assert_ne!(
+            Material::new_textured_metal(Color::new(0.8, 0.8, 0.8), checker()),
+            rough
+        );
+
+        // solid colors compare by value, and different variants never match
+        let gray = Color::new(0.5, 0.5, 0.5);
+        assert_eq!(
+            Material::new_lambertian(gray),
+            Material::new_lambertian(gray)
+        );
+        assert_ne!(
+            Material::new_lambertian(gray),
+            Material::new_lambertian(gray).with_lambertian_mode(LambertianMode::Cosine)
+        );
+        assert_ne!(
+            Material::new_lambertian(gray),
+            Material::new_isotropic(gray)
+        );
+        assert_ne!(
+            Material::new_dielectric(1.5),
+            Material::new_clean_dielectric(1.5)
+        );
+    }
 }
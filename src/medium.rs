@@ -0,0 +1,114 @@
+use std::{ops::Range, sync::Arc};
+
+use cgmath::{InnerSpace, Vector3};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use crate::{
+    aabb::Aabb,
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    Color, Float, Ray,
+};
+
+/// Fog or smoke of constant density filling a convex `boundary`
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    neg_inv_density: Float,
+    phase_function: Material,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Arc<dyn Hittable>, density: Float, albedo: Color) -> Self {
+        Self {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function: Material::new_isotropic(albedo),
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, ray: &Ray, t_range: Range<Float>) -> Option<HitRecord<'_>> {
+        let enter = self
+            .boundary
+            .hit(ray, Float::NEG_INFINITY..Float::INFINITY)?;
+        let exit = self.boundary.hit(ray, enter.t + 0.0001..Float::INFINITY)?;
+        let t_enter = enter.t.max(t_range.start).max(0.0);
+        let t_exit = exit.t.min(t_range.end);
+        if t_enter >= t_exit {
+            return None;
+        }
+
+        let ray_length = ray.direction.magnitude();
+        let distance_inside = (t_exit - t_enter) * ray_length;
+        // Hittable::hit has no RNG, so draw from one seeded by the ray to stay reproducible
+        let random: Float = ray_rng(ray).gen();
+        let hit_distance = self.neg_inv_density * (1.0 - random).ln();
+        if hit_distance > distance_inside {
+            return None;
+        }
+
+        let t = t_enter + hit_distance / ray_length;
+        Some(HitRecord {
+            front_face: true,
+            normal: Vector3::unit_x(), // arbitrary
+            ..HitRecord::new(ray, t, Vector3::unit_x(), &self.phase_function)
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+}
+
+// the cast is only needed when `Float` is `f32`
+#[allow(clippy::unnecessary_cast)]
+fn ray_rng(ray: &Ray) -> SmallRng {
+    let seed = [
+        ray.origin.x,
+        ray.origin.y,
+        ray.origin.z,
+        ray.direction.x,
+        ray.direction.y,
+        ray.direction.z,
+    ]
+    .iter()
+    .fold(0xcbf2_9ce4_8422_2325, |hash: u64, component| {
+        (hash ^ component.to_bits() as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    SmallRng::seed_from_u64(seed)
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Point3;
+
+    use super::*;
+    use crate::hittable::BoxPrim;
+
+    /// How many of a fan of rays through a unit cube of fog scatter inside it
+    fn scattered(density: Float) -> usize {
+        let boundary = BoxPrim::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 1.0, 1.0),
+            Material::new_lambertian(Color::new(0.0, 0.0, 0.0)),
+        );
+        let fog = ConstantMedium::new(Arc::new(boundary), density, Color::new(1.0, 1.0, 1.0));
+        (0..1000)
+            .filter(|&i| {
+                let y = (i as Float + 0.5) / 1000.0;
+                let ray = Ray::new(Point3::new(-1.0, y, 0.5), Vector3::unit_x());
+                fog.hit(&ray, 0.001..Float::INFINITY).is_some()
+            })
+            .count()
+    }
+
+    #[test]
+    fn denser_media_scatter_more() {
+        let (thin, dense) = (scattered(0.2), scattered(2.0));
+        // 1 - exp(-density) of the rays scatter within the unit crossing
+        assert!((thin as Float / 1000.0 - 0.181).abs() < 0.05, "{}", thin);
+        assert!((dense as Float / 1000.0 - 0.865).abs() < 0.05, "{}", dense);
+        assert!(dense > thin);
+    }
+}
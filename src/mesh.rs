@@ -0,0 +1,270 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    ops::Range,
+    path::Path,
+    sync::Arc,
+};
+
+use cgmath::{Point3, Vector3};
+use rand::{rngs::SmallRng, SeedableRng};
+
+use crate::{
+    aabb::Aabb,
+    bvh::BvhNode,
+    consts::PI,
+    hittable::{HitRecord, Hittable, HittableList, Triangle},
+    material::Material,
+    Float, Ray,
+};
+
+/// Triangulates the sphere at `center` into `segments` slices around the y axis and `rings`
+/// stacks from pole to pole, with flat faces, e.g. to check `Triangle` against `Sphere`.
+/// Faces wind counterclockwise seen from outside, so their normals point outward.
+pub fn uv_sphere(
+    center: Point3<Float>,
+    radius: Float,
+    segments: usize,
+    rings: usize,
+    material: Material,
+) -> HittableList {
+    let segments = segments.max(3);
+    let rings = rings.max(2);
+    let vertex = |ring: usize, segment: usize| {
+        let direction = if ring == 0 {
+            Vector3::unit_y()
+        } else if ring == rings {
+            -Vector3::unit_y()
+        } else {
+            let theta = PI * ring as Float / rings as Float;
+            let phi = 2.0 * PI * (segment % segments) as Float / segments as Float;
+            Vector3::new(
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                theta.sin() * phi.sin(),
+            )
+        };
+        center + radius * direction
+    };
+
+    let mut triangles = HittableList::new();
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let top_left = vertex(ring, segment);
+            let bottom_left = vertex(ring + 1, segment);
+            let bottom_right = vertex(ring + 1, segment + 1);
+            let top_right = vertex(ring, segment + 1);
+            // the quads touching a pole collapse to one triangle
+            if ring > 0 {
+                triangles.add(Triangle::new(
+                    top_left,
+                    top_right,
+                    bottom_right,
+                    material.clone(),
+                ));
+            }
+            if ring + 1 < rings {
+                triangles.add(Triangle::new(
+                    top_left,
+                    bottom_right,
+                    bottom_left,
+                    material.clone(),
+                ));
+            }
+        }
+    }
+    triangles
+}
+
+/// A flat-shaded triangle mesh stored in a BVH.
+pub struct Mesh {
+    bvh: BvhNode,
+    triangle_count: usize,
+}
+
+impl Mesh {
+    pub fn new(triangles: Vec<Triangle>) -> io::Result<Self> {
+        if triangles.is_empty() {
+            return Err(io::Error::new(ErrorKind::InvalidData, "mesh has no faces"));
+        }
+        let triangle_count = triangles.len();
+        // a fixed seed keeps the hierarchy, and therefore the render, reproducible
+        let mut rng = SmallRng::seed_from_u64(0);
+        let bvh = BvhNode::new(
+            triangles
+                .into_iter()
+                .map(|triangle| Arc::new(triangle) as Arc<dyn Hittable>)
+                .collect(),
+            &mut rng,
+        );
+        Ok(Self {
+            bvh,
+            triangle_count,
+        })
+    }
+
+    /// Loads the `v` and `f` directives of a Wavefront OBJ file, fan-triangulating polygons.
+    ///
+    /// `vn` references in faces are validated but the mesh is flat-shaded. Comments and other
+    /// directives are ignored.
+    pub fn from_obj<P: AsRef<Path>>(path: P, material: Material) -> io::Result<Self> {
+        Self::parse_obj(&fs::read_to_string(path)?, material)
+    }
+
+    pub fn parse_obj(source: &str, material: Material) -> io::Result<Self> {
+        let mut vertices = Vec::new();
+        let mut normal_count = 0;
+        let mut triangles = Vec::new();
+        for (line_index, line) in source.lines().enumerate() {
+            let line_number = line_index + 1;
+            let invalid = |message: String| {
+                io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("line {}: {}", line_number, message),
+                )
+            };
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("v") => {
+                    let mut coordinate = || -> io::Result<Float> {
+                        let token = tokens
+                            .next()
+                            .ok_or_else(|| invalid("expected three vertex coordinates".into()))?;
+                        token
+                            .parse()
+                            .map_err(|_| invalid(format!("invalid coordinate `{}`", token)))
+                    };
+                    vertices.push(Point3::new(coordinate()?, coordinate()?, coordinate()?));
+                }
+                Some("vn") => normal_count += 1,
+                Some("f") => {
+                    let indices = tokens
+                        .map(|token| {
+                            let mut references = token.split('/');
+                            let vertex = references.next().unwrap_or_default();
+                            let vertex = resolve_index(vertex, vertices.len())
+                                .map_err(|message| invalid(format!("vertex {}", message)))?;
+                            if let Some(normal) = references.nth(1) {
+                                resolve_index(normal, normal_count)
+                                    .map_err(|message| invalid(format!("normal {}", message)))?;
+                            }
+                            Ok(vertex)
+                        })
+                        .collect::<io::Result<Vec<_>>>()?;
+                    if indices.len() < 3 {
+                        return Err(invalid("face needs at least three vertices".into()));
+                    }
+                    for i in 1..indices.len() - 1 {
+                        triangles.push(Triangle::new(
+                            vertices[indices[0]],
+                            vertices[indices[i]],
+                            vertices[indices[i + 1]],
+                            material.clone(),
+                        ));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Self::new(triangles)
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.triangle_count
+    }
+}
+
+/// Converts a 1-based (or negative, relative) OBJ index into a 0-based one.
+fn resolve_index(token: &str, count: usize) -> Result<usize, String> {
+    let index = token
+        .parse::<isize>()
+        .map_err(|_| format!("index `{}` is not a number", token))?;
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        count as isize + index
+    };
+    if index == 0 || resolved < 0 || resolved >= count as isize {
+        Err(format!(
+            "index {} is out of range ({} defined so far)",
+            index, count
+        ))
+    } else {
+        Ok(resolved as usize)
+    }
+}
+
+impl Hittable for Mesh {
+    fn hit(&self, ray: &Ray, t_range: Range<Float>) -> Option<HitRecord<'_>> {
+        self.bvh.hit(ray, t_range)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bvh.bounding_box()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::InnerSpace;
+
+    use super::*;
+    use crate::Color;
+
+    fn gray() -> Material {
+        Material::new_lambertian(Color::new(0.5, 0.5, 0.5))
+    }
+
+    #[test]
+    fn loads_the_fixture_and_triangulates_quads() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/pyramid.obj");
+        let mesh = Mesh::from_obj(path, gray()).unwrap();
+        assert_eq!(mesh.triangle_count(), 6);
+        let down = Ray::new(Point3::new(0.0, 5.0, 0.0), -Vector3::unit_y());
+        let record = mesh.hit(&down, 0.001..Float::INFINITY).unwrap();
+        assert!((record.p.y - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn rejects_out_of_range_indices() {
+        let error = Mesh::parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n", gray())
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert!(error.to_string().starts_with("line 4: vertex index 4"));
+        assert!(Mesh::parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1//1 2 3\n", gray()).is_err());
+        assert!(Mesh::parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\n", gray()).is_ok());
+    }
+
+    #[test]
+    fn uv_sphere_equator_converges_to_the_radius() {
+        let center = Point3::new(1.0, 2.0, 3.0);
+        let radius = 2.0;
+        let equator_deficit = |segments: usize| {
+            let mesh = uv_sphere(center, radius, segments, segments / 2, gray());
+            (0..64)
+                .map(|i| {
+                    let phi = 2.0 * PI * (i as Float + 0.5) / 64.0;
+                    let outward = Vector3::new(phi.cos(), 0.0, phi.sin());
+                    // just above the equator, since rays along the edge there can slip through
+                    // the crack between faces in single precision
+                    let above = Vector3::new(0.0, 1e-3, 0.0);
+                    let ray = Ray::new(center + above + 10.0 * outward, -outward);
+                    let record = mesh.hit(&ray, 0.001..Float::INFINITY).unwrap();
+                    radius - (record.p - center).magnitude()
+                })
+                .fold(0.0, Float::max)
+        };
+        let deficits: Vec<Float> = [8, 32, 128]
+            .iter()
+            .map(|&segments| equator_deficit(segments))
+            .collect();
+        assert!(
+            deficits[0] > deficits[1] && deficits[1] > deficits[2],
+            "{:?}",
+            deficits
+        );
+        // the worst case is mid-edge, r (1 - cos(pi / segments))
+        assert!(deficits[2] < 1e-3 * radius, "{:?}", deficits);
+    }
+}
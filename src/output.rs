@@ -0,0 +1,189 @@
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+use image::{ColorType, ImageFormat, ImageResult};
+
+use crate::{spectrum::Spectrum, Color, Float};
+
+/// Maps HDR radiance into the displayable `[0, 1]` range.
+#[derive(Clone, Copy)]
+pub enum ToneMap {
+    /// Plain clamping
+    None,
+    /// `c / (1 + c)` per channel
+    Reinhard,
+    /// Narkowicz's fit of the ACES filmic curve
+    AcesFilmic,
+}
+
+impl ToneMap {
+    pub fn apply(self, color: Color) -> Color {
+        match self {
+            Self::None => color,
+            Self::Reinhard => color.map(|c| c / (1.0 + c)),
+            Self::AcesFilmic => color.map(|c| {
+                ((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14)).clamp(0.0, 1.0)
+            }),
+        }
+    }
+}
+
+/// Averages `color` over its samples, tone maps and gamma-corrects it and clamps each channel
+/// to a byte.
+///
+/// A `gamma` of 2.0 is the usual `sqrt` encoding and 1.0 leaves the values linear.
+pub fn color_to_rgb8<S: Spectrum>(
+    color: S,
+    samples_per_pixel: usize,
+    gamma: Float,
+    tone_map: ToneMap,
+) -> [u8; 3] {
+    let color = tone_map.apply(color.to_rgb() / samples_per_pixel as Float);
+    [color.x, color.y, color.z]
+        .map(|channel| (256.0 * channel.powf(1.0 / gamma).clamp(0.0, 0.999)) as u8)
+}
+
+/// Writes one pixel as a line of a plain-text P3 PPM.
+pub fn write_color<S: Spectrum, W: Write>(
+    mut writer: W,
+    color: S,
+    samples_per_pixel: usize,
+    gamma: Float,
+    tone_map: ToneMap,
+) {
+    let [r, g, b] = color_to_rgb8(color, samples_per_pixel, gamma, tone_map);
+    writeln!(writer, "{} {} {}", r, g, b).expect("Couldn't write a color");
+}
+
+/// Writes `pixels` as a binary P6 PPM.
+pub fn write_ppm_binary<W: Write>(
+    mut writer: W,
+    pixels: &[Color],
+    width: usize,
+    height: usize,
+    samples_per_pixel: usize,
+    gamma: Float,
+    tone_map: ToneMap,
+) -> io::Result<()> {
+    write_ppm_header(&mut writer, width, height)?;
+    write_rgb8(&mut writer, pixels, samples_per_pixel, gamma, tone_map)?;
+    writer.flush()
+}
+
+/// Writes the header of a binary P6 PPM, to be followed by `width * height` pixels written
+/// with `write_rgb8`.
+pub fn write_ppm_header<W: Write>(mut writer: W, width: usize, height: usize) -> io::Result<()> {
+    write!(writer, "P6\n{} {}\n255\n", width, height)
+}
+
+/// Writes `pixels` as raw 8-bit RGB, e.g. one row of a streamed P6 PPM.
+pub fn write_rgb8<W: Write>(
+    mut writer: W,
+    pixels: &[Color],
+    samples_per_pixel: usize,
+    gamma: Float,
+    tone_map: ToneMap,
+) -> io::Result<()> {
+    writer.write_all(&to_rgb8_buffer(pixels, samples_per_pixel, gamma, tone_map))
+}
+
+/// Saves `pixels` as an 8-bit RGB PNG.
+pub fn save_png<P: AsRef<Path>>(
+    path: P,
+    pixels: &[Color],
+    width: usize,
+    height: usize,
+    samples_per_pixel: usize,
+    gamma: Float,
+    tone_map: ToneMap,
+) -> ImageResult<()> {
+    image::save_buffer_with_format(
+        path,
+        &to_rgb8_buffer(pixels, samples_per_pixel, gamma, tone_map),
+        width as u32,
+        height as u32,
+        ColorType::Rgb8,
+        ImageFormat::Png,
+    )
+}
+
+fn to_rgb8_buffer(
+    pixels: &[Color],
+    samples_per_pixel: usize,
+    gamma: Float,
+    tone_map: ToneMap,
+) -> Vec<u8> {
+    pixels
+        .iter()
+        .flat_map(|color| color_to_rgb8(*color, samples_per_pixel, gamma, tone_map))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use image::GenericImageView;
+
+    use super::*;
+
+    #[test]
+    fn binary_ppm_has_a_header_and_three_bytes_per_pixel() {
+        let pixels = vec![Color::new(1.0, 0.0, 0.25); 6];
+        let mut buffer = Vec::new();
+        write_ppm_binary(&mut buffer, &pixels, 3, 2, 1, 2.0, ToneMap::None).unwrap();
+        let header = b"P6\n3 2\n255\n";
+        assert_eq!(&buffer[..header.len()], header);
+        let body = &buffer[header.len()..];
+        assert_eq!(body.len(), 3 * pixels.len());
+        assert!(body.chunks(3).all(|pixel| pixel == [255, 0, 128]));
+    }
+
+    #[test]
+    fn saved_png_reloads_with_its_dimensions() {
+        let path = std::env::temp_dir().join(format!("ray-tracing-{}.png", std::process::id()));
+        let pixels = vec![Color::new(0.5, 0.5, 0.5); 4 * 3];
+        save_png(&path, &pixels, 4, 3, 1, 2.0, ToneMap::None).unwrap();
+        let image = image::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(image.dimensions(), (4, 3));
+
+        let missing_directory = std::env::temp_dir().join("ray-tracing-missing/image.png");
+        assert!(save_png(missing_directory, &pixels, 4, 3, 1, 2.0, ToneMap::None).is_err());
+    }
+
+    #[test]
+    fn converts_black_white_and_hdr_colors_to_bytes() {
+        let convert = |color| color_to_rgb8(color, 4, 2.0, ToneMap::None);
+        assert_eq!(convert(Color::new(0.0, 0.0, 0.0)), [0, 0, 0]);
+        assert_eq!(convert(Color::new(4.0, 4.0, 4.0)), [255, 255, 255]);
+        assert_eq!(convert(Color::new(40.0, 0.0, 400.0)), [255, 0, 255]);
+    }
+
+    #[test]
+    fn gamma_two_is_the_square_root_and_one_is_linear() {
+        for i in 0..=100 {
+            let channel = i as Float / 100.0;
+            let color = Color::new(channel, channel, channel);
+            let expected = (256.0 * channel.sqrt().clamp(0.0, 0.999)) as u8;
+            assert_eq!(color_to_rgb8(color, 1, 2.0, ToneMap::None), [expected; 3]);
+            let linear = (256.0 * channel.clamp(0.0, 0.999)) as u8;
+            assert_eq!(color_to_rgb8(color, 1, 1.0, ToneMap::None), [linear; 3]);
+        }
+    }
+
+    #[test]
+    fn tone_maps_bring_hdr_radiance_below_one() {
+        let bright = Color::new(10.0, 10.0, 10.0);
+        let reinhard = ToneMap::Reinhard.apply(bright);
+        assert!((reinhard.x - 10.0 / 11.0).abs() < 1e-6);
+        let aces = ToneMap::AcesFilmic.apply(bright);
+        assert!(aces.x > 0.9 && aces.x <= 1.0);
+        assert_eq!(ToneMap::None.apply(bright), bright);
+        assert_eq!(
+            color_to_rgb8(bright, 1, 2.0, ToneMap::None),
+            [255, 255, 255]
+        );
+        assert!(color_to_rgb8(bright, 1, 1.0, ToneMap::Reinhard)[0] < 255);
+    }
+}
@@ -0,0 +1,118 @@
+use cgmath::{InnerSpace, Point3, Vector3};
+use rand::{distributions::Uniform, prelude::Distribution, seq::SliceRandom, Rng};
+
+use crate::Float;
+
+const POINT_COUNT: usize = 256;
+
+/// Gradient noise over random unit vectors hashed by three permutation tables
+pub struct Perlin {
+    random_vectors: Vec<Vector3<Float>>,
+    permutation_x: Vec<usize>,
+    permutation_y: Vec<usize>,
+    permutation_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new<R: Rng>(rng: &mut R) -> Self {
+        let distribution = Uniform::from(-1.0..1.0);
+        let random_vectors = (0..POINT_COUNT)
+            .map(|_| loop {
+                let v = Vector3::new(
+                    distribution.sample(rng),
+                    distribution.sample(rng),
+                    distribution.sample(rng),
+                );
+                let length2 = v.magnitude2();
+                if length2 > 1e-8 && length2 <= 1.0 {
+                    break v.normalize();
+                }
+            })
+            .collect();
+        let mut permutation = || {
+            let mut permutation: Vec<_> = (0..POINT_COUNT).collect();
+            permutation.shuffle(rng);
+            permutation
+        };
+        let permutation_x = permutation();
+        let permutation_y = permutation();
+        let permutation_z = permutation();
+        Self {
+            random_vectors,
+            permutation_x,
+            permutation_y,
+            permutation_z,
+        }
+    }
+
+    /// Smooth noise in `[-1, 1]`
+    pub fn noise(&self, p: Point3<Float>) -> Float {
+        let (i, j, k) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (u, v, w) = (p.x - i, p.y - j, p.z - k);
+        let (i, j, k) = (i as i64, j as i64, k as i64);
+        // Hermite smoothing
+        let (uu, vv, ww) = (
+            u * u * (3.0 - 2.0 * u),
+            v * v * (3.0 - 2.0 * v),
+            w * w * (3.0 - 2.0 * w),
+        );
+
+        let mut accumulated = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let gradient = self.random_vectors[self.permutation_x
+                        [((i + di) & 255) as usize]
+                        ^ self.permutation_y[((j + dj) & 255) as usize]
+                        ^ self.permutation_z[((k + dk) & 255) as usize]];
+                    let (di, dj, dk) = (di as Float, dj as Float, dk as Float);
+                    let weight = Vector3::new(u - di, v - dj, w - dk);
+                    accumulated += (di * uu + (1.0 - di) * (1.0 - uu))
+                        * (dj * vv + (1.0 - dj) * (1.0 - vv))
+                        * (dk * ww + (1.0 - dk) * (1.0 - ww))
+                        * gradient.dot(weight);
+                }
+            }
+        }
+        accumulated
+    }
+
+    /// Sum of the absolute noise over `octaves` octaves, each at double the frequency and half
+    /// the weight of the previous one
+    pub fn turbulence(&self, p: Point3<Float>, octaves: usize) -> Float {
+        let mut accumulated = 0.0;
+        let mut p = p;
+        let mut weight = 1.0;
+        for _ in 0..octaves {
+            accumulated += weight * self.noise(p).abs();
+            weight *= 0.5;
+            p *= 2.0;
+        }
+        accumulated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn noise_is_bounded_and_deterministic() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let perlin = Perlin::new(&mut rng);
+        let distribution = Uniform::from(-50.0..50.0);
+        for _ in 0..10_000 {
+            let p = Point3::new(
+                distribution.sample(&mut rng),
+                distribution.sample(&mut rng),
+                distribution.sample(&mut rng),
+            );
+            let noise = perlin.noise(p);
+            assert!((-1.0..=1.0).contains(&noise), "{} at {:?}", noise, p);
+            assert_eq!(perlin.noise(p), noise);
+            assert!(perlin.turbulence(p, 7) >= 0.0);
+        }
+    }
+}
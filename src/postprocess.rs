@@ -0,0 +1,55 @@
+use crate::{Color, Float};
+
+/// Relative luminance of a linear sRGB color.
+pub fn luminance(color: Color) -> Float {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
+/// Scales `pixels` so that their mean luminance becomes `key` (0.18 is the usual middle gray).
+///
+/// `pixels` must hold averaged radiance, not per-pixel sums of samples. A black image is left
+/// untouched.
+pub fn auto_exposure(pixels: &mut [Color], key: Float) {
+    if pixels.is_empty() {
+        return;
+    }
+    let mean = pixels.iter().map(|&pixel| luminance(pixel)).sum::<Float>() / pixels.len() as Float;
+    if mean > 0.0 {
+        let scale = key / mean;
+        pixels.iter_mut().for_each(|pixel| *pixel *= scale);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::InnerSpace;
+
+    use super::*;
+
+    #[test]
+    fn auto_exposure_scales_the_mean_luminance_to_the_key() {
+        let mut pixels = vec![
+            Color::new(1.0, 0.5, 0.25),
+            Color::new(4.0, 4.0, 4.0),
+            Color::new(0.0, 0.0, 0.0),
+            Color::new(0.2, 0.9, 0.1),
+        ];
+        let original = pixels.clone();
+        auto_exposure(&mut pixels, 0.18);
+        let mean = pixels.iter().map(|&pixel| luminance(pixel)).sum::<Float>() / 4.0;
+        assert!((mean - 0.18).abs() < 1e-6);
+        // one common factor, so hues are kept
+        let scale = pixels[1].x / original[1].x;
+        for (pixel, original) in pixels.iter().zip(&original) {
+            assert!((pixel - original * scale).magnitude() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn auto_exposure_leaves_black_and_empty_images_alone() {
+        let mut pixels = vec![Color::new(0.0, 0.0, 0.0); 3];
+        auto_exposure(&mut pixels, 0.18);
+        assert_eq!(pixels, vec![Color::new(0.0, 0.0, 0.0); 3]);
+        auto_exposure(&mut [], 0.18);
+    }
+}
@@ -0,0 +1,125 @@
+use std::{
+    io::{self, IsTerminal, Write},
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+const BAR_WIDTH: usize = 40;
+
+/// Completion bar with an ETA on stderr, safe to advance from many threads.
+///
+/// Redraws are throttled; when stderr is not a terminal, plain lines are printed less often
+/// instead of redrawing a bar in place.
+pub struct Progress {
+    total: usize,
+    finished: AtomicUsize,
+    start: Instant,
+    /// Milliseconds after `start` of the last redraw
+    last_draw: AtomicU64,
+    interval: Duration,
+    is_terminal: bool,
+}
+
+impl Progress {
+    pub fn new(total: usize) -> Self {
+        let is_terminal = io::stderr().is_terminal();
+        Self {
+            total,
+            finished: AtomicUsize::new(0),
+            start: Instant::now(),
+            last_draw: AtomicU64::new(0),
+            interval: if is_terminal {
+                Duration::from_millis(250)
+            } else {
+                Duration::from_secs(5)
+            },
+            is_terminal,
+        }
+    }
+
+    /// Records one more finished unit of work.
+    pub fn advance(&self) {
+        let finished = self.finished.fetch_add(1, Ordering::Relaxed) + 1;
+        let now = self.start.elapsed().as_millis() as u64;
+        let last_draw = self.last_draw.load(Ordering::Relaxed);
+        if now.saturating_sub(last_draw) >= self.interval.as_millis() as u64
+            && self
+                .last_draw
+                .compare_exchange(last_draw, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.draw(finished);
+        }
+    }
+
+    /// Draws the final state and ends the line.
+    pub fn finish(&self) {
+        self.draw(self.finished.load(Ordering::Relaxed));
+        if self.is_terminal {
+            eprintln!();
+        }
+    }
+
+    fn draw(&self, finished: usize) {
+        let fraction = if self.total == 0 {
+            1.0
+        } else {
+            finished as f64 / self.total as f64
+        };
+        let eta = if finished == 0 {
+            "?".to_string()
+        } else {
+            format_duration(
+                self.start
+                    .elapsed()
+                    .mul_f64((self.total - finished) as f64 / finished as f64),
+            )
+        };
+        let mut stderr = io::stderr().lock();
+        // progress output is best effort
+        let _ = if self.is_terminal {
+            let filled = (fraction * BAR_WIDTH as f64) as usize;
+            write!(
+                stderr,
+                "\r[{}{}] {:5.1}% ETA {:<10}",
+                "#".repeat(filled),
+                "-".repeat(BAR_WIDTH - filled),
+                100.0 * fraction,
+                eta,
+            )
+        } else {
+            writeln!(stderr, "{:5.1}% ETA {}", 100.0 * fraction, eta)
+        };
+        let _ = stderr.flush();
+    }
+}
+
+/// Formats `duration` as e.g. `1h02m03s`, `2m03s` or `3s`.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}h{:02}m{:02}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m{:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_durations_with_the_largest_unit_first() {
+        assert_eq!(format_duration(Duration::from_millis(3900)), "3s");
+        assert_eq!(format_duration(Duration::from_secs(123)), "2m03s");
+        assert_eq!(format_duration(Duration::from_secs(3600)), "1h00m00s");
+        assert_eq!(format_duration(Duration::from_secs(3723)), "1h02m03s");
+        assert_eq!(
+            format_duration(Duration::from_secs(100 * 3600)),
+            "100h00m00s"
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use cgmath::{InnerSpace, Vector3};
+use rand::{distributions::Uniform, prelude::Distribution, Rng};
+
+use crate::{consts::PI, Float};
+
+/// Uniformly distributed point inside the unit sphere.
+pub fn random_in_unit_sphere<R: Rng>(rng: &mut R) -> Vector3<Float> {
+    let distribution = Uniform::from(-1.0..1.0);
+    loop {
+        let v = Vector3::new(
+            distribution.sample(rng),
+            distribution.sample(rng),
+            distribution.sample(rng),
+        );
+        if v.magnitude2() <= 1.0 {
+            return v;
+        }
+    }
+}
+
+/// Uniformly distributed point on the unit sphere.
+pub fn random_unit_vector<R: Rng>(rng: &mut R) -> Vector3<Float> {
+    let distribution = Uniform::from(0.0..1.0);
+    let u = distribution.sample(rng);
+    let v = distribution.sample(rng);
+    unit_vector_from_samples(u, v)
+}
+
+/// Uniformly distributed point inside the unit disk on the xy plane.
+pub fn random_in_unit_disk<R: Rng>(rng: &mut R) -> Vector3<Float> {
+    let distribution = Uniform::from(-1.0..1.0);
+    loop {
+        let x = distribution.sample(rng);
+        let y = distribution.sample(rng);
+        if x * x + y * y <= 1.0 {
+            return Vector3::new(x, y, 0.0);
+        }
+    }
+}
+
+/// Unit vector on the +z hemisphere with a density proportional to its z component.
+pub fn random_cosine_direction<R: Rng>(rng: &mut R) -> Vector3<Float> {
+    let distribution = Uniform::from(0.0..1.0);
+    let u = distribution.sample(rng);
+    let v = distribution.sample(rng);
+    cosine_direction_from_samples(u, v)
+}
+
+/// Maps two uniform samples in `[0, 1)` to a point uniformly distributed on the unit sphere.
+pub fn unit_vector_from_samples(u: Float, v: Float) -> Vector3<Float> {
+    let z = 1.0 - 2.0 * u;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * v;
+    Vector3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// Maps three uniform samples in `[0, 1)` to a point uniformly distributed inside the unit ball.
+pub fn ball_point_from_samples(u: Float, v: Float, w: Float) -> Vector3<Float> {
+    w.cbrt() * unit_vector_from_samples(u, v)
+}
+
+/// Maps two uniform samples in `[0, 1)` to a cosine-distributed unit vector around +z.
+pub fn cosine_direction_from_samples(u: Float, v: Float) -> Vector3<Float> {
+    let phi = 2.0 * PI * u;
+    let r = v.sqrt();
+    Vector3::new(r * phi.cos(), r * phi.sin(), (1.0 - v).max(0.0).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::SmallRng, SeedableRng};
+
+    use super::*;
+
+    const SAMPLES: usize = 20_000;
+
+    #[test]
+    fn disk_samples_cover_all_quadrants_around_the_origin() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut quadrants = [0; 4];
+        let mut sum = Vector3::new(0.0, 0.0, 0.0);
+        for _ in 0..SAMPLES {
+            let p = random_in_unit_disk(&mut rng);
+            assert!(p.magnitude2() <= 1.0 && p.z == 0.0);
+            quadrants[(p.x < 0.0) as usize * 2 + (p.y < 0.0) as usize] += 1;
+            sum += p;
+        }
+        let mean = sum / SAMPLES as Float;
+        assert!(mean.magnitude() < 0.02, "mean {:?}", mean);
+        for &count in &quadrants {
+            assert!(count > SAMPLES / 5, "quadrants {:?}", quadrants);
+        }
+    }
+
+    #[test]
+    fn sphere_samples_stay_inside_and_unit_vectors_on_the_sphere() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut sum = Vector3::new(0.0, 0.0, 0.0);
+        for _ in 0..SAMPLES {
+            assert!(random_in_unit_sphere(&mut rng).magnitude2() <= 1.0);
+            let v = random_unit_vector(&mut rng);
+            assert!((v.magnitude() - 1.0).abs() < 1e-5);
+            sum += v;
+        }
+        let mean = sum / SAMPLES as Float;
+        assert!(mean.magnitude() < 0.02, "mean {:?}", mean);
+    }
+
+    #[test]
+    fn cosine_directions_are_unit_and_weighted_toward_z() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut z_sum = 0.0;
+        for _ in 0..SAMPLES {
+            let v = random_cosine_direction(&mut rng);
+            assert!((v.magnitude() - 1.0).abs() < 1e-5 && v.z >= 0.0);
+            z_sum += v.z;
+        }
+        // E[cos] under a cosine-weighted density is 2/3, against 1/2 for uniform directions
+        let mean = z_sum / SAMPLES as Float;
+        assert!((mean - 2.0 / 3.0).abs() < 0.01, "mean z {}", mean);
+    }
+
+    #[test]
+    fn unit_vectors_from_samples_average_to_zero_on_every_axis() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut sum = Vector3::new(0.0, 0.0, 0.0);
+        for _ in 0..SAMPLES {
+            let v = unit_vector_from_samples(rng.gen(), rng.gen());
+            assert!((v.magnitude() - 1.0).abs() < 1e-5);
+            sum += v;
+        }
+        let mean = sum / SAMPLES as Float;
+        for axis in 0..3 {
+            assert!(mean[axis].abs() < 0.02, "mean {:?}", mean);
+        }
+    }
+}
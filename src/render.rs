@@ -0,0 +1,1657 @@
+use std::{io, path::Path, sync::Arc};
+
+use cgmath::{ElementWise, InnerSpace, Point3};
+use rand::{distributions::Uniform, prelude::Distribution, rngs::SmallRng, Rng, SeedableRng};
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefMutIterator, ParallelIterator,
+};
+
+use crate::{
+    background::Background,
+    camera::Camera,
+    consts::PI,
+    hittable::{HitRecord, Hittable, DEFAULT_EPSILON},
+    postprocess::luminance,
+    progress::Progress,
+    spectrum::Spectrum,
+    tile::{Checkpoint, Tiles},
+    Color, Float, Ray,
+};
+
+pub struct RenderSettings {
+    pub width: usize,
+    pub height: usize,
+    pub samples_per_pixel: usize,
+    pub max_depth: usize,
+    pub fog: Fog,
+    pub sample_strategy: SampleStrategy,
+    /// Bounces after which paths are randomly terminated by Russian roulette; `None` disables it
+    pub russian_roulette_depth: Option<usize>,
+    /// Caps every channel of each sample to suppress fireflies, at the cost of a little bias
+    pub clamp_radiance: Option<Float>,
+    /// Mixed into every pixel's RNG seed; each value gives an independent noise pattern
+    pub seed: u64,
+    /// Stops sampling converged pixels early instead of taking `samples_per_pixel` everywhere
+    pub adaptive_sampling: Option<AdaptiveSampling>,
+    /// Emitters sampled directly at diffuse bounces, e.g. a `HittableList` of several lights.
+    /// Every emitter in the scene must be part of it, since light reached by the bounce itself
+    /// is discounted.
+    pub lights: Option<Arc<dyn Hittable>>,
+    pub light_sampling: LightSampling,
+    pub mode: RenderMode,
+}
+
+/// What each pixel shows. Everything but `Beauty` stops at the first hit, for debugging and as
+/// denoiser inputs.
+#[derive(Clone, Copy)]
+pub enum RenderMode {
+    /// Full path tracing
+    Beauty,
+    /// Surface normal remapped from `-1..1` to `0..1`; black where nothing is hit
+    Normal,
+    /// Hit distance divided by `far` and clamped to `0..1`; white where nothing is hit
+    Depth { far: Float },
+    /// Base color of the material; the background where nothing is hit
+    Albedo,
+}
+
+/// How `RenderSettings::lights` are used at diffuse bounces
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LightSampling {
+    /// Ignores `lights`: light is only found by following the BRDF
+    Brdf,
+    /// Next-event estimation: a shadow ray towards the lights gathers all direct light
+    NextEvent,
+    /// Combines light and BRDF samples with the power heuristic
+    MultipleImportance,
+}
+
+/// Power heuristic weight of a sample drawn with density `pdf` against one of density `other_pdf`
+fn power_heuristic(pdf: Float, other_pdf: Float) -> Float {
+    let pdf2 = pdf * pdf;
+    let sum = pdf2 + other_pdf * other_pdf;
+    if sum > 0.0 {
+        pdf2 / sum
+    } else {
+        0.0
+    }
+}
+
+/// Samples a pixel in batches of `min_samples` until the standard error of its mean luminance
+/// drops below `tolerance` times that mean, or `max_samples` is reached.
+pub struct AdaptiveSampling {
+    pub min_samples: usize,
+    pub max_samples: usize,
+    pub tolerance: Float,
+}
+
+impl AdaptiveSampling {
+    fn converged(&self, count: usize, luminance_sum: Float, luminance_sum2: Float) -> bool {
+        if count < self.min_samples.max(2) {
+            return false;
+        }
+        let n = count as Float;
+        let mean = luminance_sum / n;
+        let variance = ((luminance_sum2 - n * mean * mean) / (n - 1.0)).max(0.0);
+        // the floor keeps black pixels from never converging
+        (variance / n).sqrt() <= self.tolerance * mean.max(1e-3)
+    }
+}
+
+/// How the samples of a pixel are spread over its area.
+#[derive(Clone, Copy)]
+pub enum SampleStrategy {
+    Uniform,
+    /// Jitters each sample inside its own cell of an `n x n` grid; falls back to uniform
+    /// sampling unless `samples_per_pixel` is a perfect square.
+    Stratified,
+}
+
+impl SampleStrategy {
+    /// Cells per side of the jitter grid, `None` for uniform sampling
+    fn grid_size(self, samples_per_pixel: usize) -> Option<usize> {
+        match self {
+            Self::Uniform => None,
+            Self::Stratified => {
+                let n = (samples_per_pixel as Float).sqrt().round() as usize;
+                if n > 0 && n * n == samples_per_pixel {
+                    Some(n)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Homogeneous exponential fog blended over every path segment.
+pub struct Fog {
+    pub density: Float,
+    pub color: Color,
+}
+
+impl Fog {
+    fn transmittance(&self, distance: Float) -> Float {
+        if self.density > 0.0 {
+            (-self.density * distance).exp()
+        } else {
+            1.0
+        }
+    }
+
+    fn apply<S: Spectrum>(&self, radiance: S, transmittance: Float) -> S {
+        radiance * transmittance + S::from_rgb(self.color) * (1.0 - transmittance)
+    }
+}
+
+/// Traces `ray` for at most `settings.max_depth` bounces, or only to its first hit for the
+/// modes other than `RenderMode::Beauty`.
+///
+/// After `russian_roulette_depth` bounces a path survives each further bounce with a probability
+/// equal to the largest channel of its throughput, and survivors are reweighted to stay unbiased.
+pub fn ray_color<S: Spectrum, H: Hittable, R: Rng>(
+    ray: &Ray,
+    hittable: &H,
+    background: &Background,
+    settings: &RenderSettings,
+    rng: &mut R,
+) -> S {
+    match settings.mode {
+        RenderMode::Beauty => {}
+        mode => return S::from_rgb(first_hit_color(ray, hittable, background, mode)),
+    }
+
+    let mut path = PathState::new(ray.clone(), settings);
+    while path.step(hittable, background, settings, rng) {}
+    path.accumulated
+}
+
+/// A path being traced, advanced one segment at a time
+struct PathState<S> {
+    ray: Ray,
+    /// Bounces left
+    depth: usize,
+    // light gathered so far, and the fraction of what comes next that reaches the camera
+    accumulated: S,
+    throughput: S,
+    /// Set by an internal reflection in clean glass, cleared once the path hits anything but
+    /// the inside of that glass
+    hide_background: bool,
+    /// Origin and BRDF density of the last bounce if it was a diffuse one that sampled the lights
+    last_diffuse: Option<(Point3<Float>, Float)>,
+    t_min: Float,
+}
+
+impl<S: Spectrum> PathState<S> {
+    fn new(ray: Ray, settings: &RenderSettings) -> Self {
+        Self {
+            ray,
+            depth: settings.max_depth,
+            accumulated: S::from_rgb(Color::new(0.0, 0.0, 0.0)),
+            throughput: S::from_rgb(Color::new(1.0, 1.0, 1.0)),
+            hide_background: false,
+            last_diffuse: None,
+            t_min: DEFAULT_EPSILON,
+        }
+    }
+
+    /// Traces the next segment of the path; returns whether it goes on.
+    fn step<H: Hittable, R: Rng>(
+        &mut self,
+        hittable: &H,
+        background: &Background,
+        settings: &RenderSettings,
+        rng: &mut R,
+    ) -> bool {
+        if self.depth == 0 {
+            return false;
+        }
+        let fog = &settings.fog;
+        let lights = match settings.light_sampling {
+            LightSampling::Brdf => None,
+            _ => settings.lights.as_deref(),
+        };
+        let ray = &self.ray;
+        let record = match hittable.hit(ray, self.t_min..Float::INFINITY) {
+            Some(record) => record,
+            None => {
+                let background = if self.hide_background {
+                    Color::new(1.0, 1.0, 1.0)
+                } else {
+                    background.color(ray.direction)
+                };
+                self.accumulated = self.accumulated
+                    + self.throughput.mul_spectrum(
+                        fog.apply(S::from_rgb(background), fog.transmittance(Float::INFINITY)),
+                    );
+                return false;
+            }
+        };
+
+        let transmittance = fog.transmittance(record.t * ray.direction.magnitude());
+        let emitted_weight = match (self.last_diffuse, lights) {
+            (Some((origin, brdf_pdf)), Some(lights)) => match settings.light_sampling {
+                LightSampling::MultipleImportance => {
+                    power_heuristic(brdf_pdf, lights.pdf_value(origin, ray.direction))
+                }
+                _ => 0.0,
+            },
+            _ => 1.0,
+        };
+        let emitted = S::from_rgb(record.material.emitted() * emitted_weight);
+        self.accumulated = self.accumulated
+            + self
+                .throughput
+                .mul_spectrum(fog.apply(emitted, transmittance));
+
+        let diffuse_albedo = lights.and(record.material.diffuse_albedo(&record));
+        if let (Some(lights), Some(albedo)) = (lights, diffuse_albedo) {
+            let direct = direct_light(
+                hittable,
+                lights,
+                &record,
+                ray,
+                albedo,
+                settings.light_sampling == LightSampling::MultipleImportance,
+                rng,
+            );
+            self.accumulated = self.accumulated
+                + self.throughput.mul_spectrum(S::from_rgb(direct)) * transmittance;
+        }
+
+        let (scattered, attenuation) = match record.material.scatter::<S, _>(ray, &record, rng) {
+            Some(scattered) => scattered,
+            None => return false,
+        };
+        // an internal reflection hides the background until the path leaves the glass and
+        // hits something else
+        self.hide_background = record.material.hides_background(&record, &scattered)
+            || (self.hide_background && !record.front_face);
+        self.last_diffuse = diffuse_albedo.map(|_| {
+            let cosine = scattered.direction.normalize().dot(record.normal);
+            (record.p, cosine.max(0.0) / PI)
+        });
+        self.t_min = record.t_min;
+        self.ray = scattered;
+        self.depth -= 1;
+        self.throughput = self.throughput.mul_spectrum(attenuation) * transmittance;
+
+        if settings
+            .russian_roulette_depth
+            .is_some_and(|roulette_depth| settings.max_depth - self.depth > roulette_depth)
+        {
+            let rgb = self.throughput.to_rgb();
+            let survival = rgb.x.max(rgb.y).max(rgb.z).min(1.0);
+            if survival < 1.0 {
+                if rng.gen::<Float>() >= survival {
+                    return false;
+                }
+                self.throughput = self.throughput / survival;
+            }
+        }
+        true
+    }
+}
+
+/// The arbitrary output variable `mode` at the first hit of `ray`.
+fn first_hit_color<H: Hittable>(
+    ray: &Ray,
+    hittable: &H,
+    background: &Background,
+    mode: RenderMode,
+) -> Color {
+    let record = hittable.hit(ray, DEFAULT_EPSILON..Float::INFINITY);
+    match (mode, record) {
+        (RenderMode::Normal, Some(record)) => 0.5 * (record.normal + Color::new(1.0, 1.0, 1.0)),
+        (RenderMode::Normal, None) => Color::new(0.0, 0.0, 0.0),
+        (RenderMode::Depth { far }, Some(record)) => {
+            let depth = (record.t * ray.direction.magnitude() / far).clamp(0.0, 1.0);
+            Color::new(depth, depth, depth)
+        }
+        (RenderMode::Depth { .. }, None) => Color::new(1.0, 1.0, 1.0),
+        (RenderMode::Albedo, Some(record)) => record.material.albedo(&record),
+        (RenderMode::Albedo, None) | (RenderMode::Beauty, _) => background.color(ray.direction),
+    }
+}
+
+/// Light reflected by a lambertian surface of `albedo` at `record`, estimated with one shadow
+/// ray towards a point sampled on `lights`, optionally weighted against BRDF sampling.
+fn direct_light<H: Hittable, R: Rng>(
+    world: &H,
+    lights: &dyn Hittable,
+    record: &HitRecord,
+    ray: &Ray,
+    albedo: Color,
+    multiple_importance: bool,
+    rng: &mut R,
+) -> Color {
+    let black = Color::new(0.0, 0.0, 0.0);
+    let distribution = Uniform::from(0.0..1.0);
+    let direction = lights.random(record.p, distribution.sample(rng), distribution.sample(rng));
+    let cosine = direction.normalize().dot(record.normal);
+    let pdf = lights.pdf_value(record.p, direction);
+    if cosine <= 0.0 || pdf <= 0.0 {
+        return black;
+    }
+    let weight = if multiple_importance {
+        power_heuristic(pdf, cosine / PI)
+    } else {
+        1.0
+    };
+    let shadow_ray = Ray::new(record.p, direction).with_time(ray.time);
+    match world.hit(&shadow_ray, record.t_min..Float::INFINITY) {
+        Some(light) => {
+            light.material.emitted().mul_element_wise(albedo) * (weight * cosine / (PI * pdf))
+        }
+        None => black,
+    }
+}
+
+/// Renders every pixel in parallel and returns the sum of its samples, row by row from the top.
+/// With adaptive sampling the sums are rescaled to `samples_per_pixel` samples.
+///
+/// Pixels are independent, so the work spreads over all cores. Each pixel seeds its own RNG
+/// from its index and `settings.seed`, so the output does not depend on scheduling.
+pub fn render<H: Hittable>(
+    camera: &Camera,
+    world: &H,
+    background: &Background,
+    settings: &RenderSettings,
+) -> Vec<Color> {
+    let progress = Progress::new(settings.width * settings.height);
+    let pixels = (0..settings.width * settings.height)
+        .into_par_iter()
+        .map(|index| {
+            let color = render_pixel(index, camera, world, background, settings);
+            progress.advance();
+            color
+        })
+        .collect();
+    progress.finish();
+    pixels
+}
+
+/// Like `render`, but renders one row at a time, from the top, and hands each to `on_row` as
+/// soon as it is finished, so only a single row is kept in memory. The pixels of a row still
+/// render in parallel and come out the same as with `render`.
+pub fn render_rows<H: Hittable, F: FnMut(&[Color]) -> io::Result<()>>(
+    camera: &Camera,
+    world: &H,
+    background: &Background,
+    settings: &RenderSettings,
+    mut on_row: F,
+) -> io::Result<()> {
+    let width = settings.width;
+    let progress = Progress::new(settings.height);
+    let mut row = Vec::with_capacity(width);
+    for y in 0..settings.height {
+        (y * width..(y + 1) * width)
+            .into_par_iter()
+            .map(|index| render_pixel(index, camera, world, background, settings))
+            .collect_into_vec(&mut row);
+        on_row(&row)?;
+        progress.advance();
+    }
+    progress.finish();
+    Ok(())
+}
+
+/// Like `render`, but renders `tile_size` square tiles in parallel and saves each finished one
+/// to the `Checkpoint` at `checkpoint_path`. Tiles already there are skipped, so an interrupted
+/// render resumes where it stopped and gives the same image as an uninterrupted one.
+pub fn render_tiled<H: Hittable, P: AsRef<Path>>(
+    camera: &Camera,
+    world: &H,
+    background: &Background,
+    settings: &RenderSettings,
+    tile_size: usize,
+    checkpoint_path: P,
+) -> io::Result<Vec<Color>> {
+    let tiles = Tiles::new(settings.width, settings.height, tile_size);
+    let (checkpoint, finished) = Checkpoint::open(
+        checkpoint_path,
+        tiles,
+        settings.samples_per_pixel,
+        settings.seed,
+    )?;
+    let remaining: Vec<usize> = (0..tiles.count())
+        .filter(|tile| !finished.contains_key(tile))
+        .collect();
+    let progress = Progress::new(remaining.len());
+    let rendered = remaining
+        .into_par_iter()
+        .map(|tile| {
+            let pixels: Vec<Color> = tiles
+                .pixels(tile)
+                .map(|index| render_pixel(index, camera, world, background, settings))
+                .collect();
+            checkpoint.save(tile, &pixels)?;
+            progress.advance();
+            Ok((tile, pixels))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    progress.finish();
+
+    let mut image = vec![Color::new(0.0, 0.0, 0.0); settings.width * settings.height];
+    for (tile, pixels) in finished.into_iter().chain(rendered) {
+        for (index, color) in tiles.pixels(tile).zip(pixels) {
+            image[index] = color;
+        }
+    }
+    Ok(image)
+}
+
+/// Sum of the samples of the pixel at `index`, counted row by row from the top.
+fn render_pixel<H: Hittable>(
+    index: usize,
+    camera: &Camera,
+    world: &H,
+    background: &Background,
+    settings: &RenderSettings,
+) -> Color {
+    let grid_size = grid_size(settings);
+    let mut rng = pixel_rng(index, settings);
+    let mut sample = |sample_index: usize| {
+        let ray = primary_ray(index, sample_index, grid_size, camera, settings, &mut rng);
+        let color = ray_color::<Color, _, _>(&ray, world, background, settings, &mut rng);
+        clamp_sample(color, settings)
+    };
+    match &settings.adaptive_sampling {
+        None => (0..settings.samples_per_pixel).map(&mut sample).sum(),
+        Some(adaptive) => {
+            let (sum, count) = sample_adaptively(adaptive, sample);
+            // rescale so callers can keep dividing by `samples_per_pixel`
+            sum * (settings.samples_per_pixel as Float / count.max(1) as Float)
+        }
+    }
+}
+
+/// Draws samples of one pixel with `sample` until `adaptive` deems it converged, returning
+/// their sum and count.
+fn sample_adaptively<F: FnMut(usize) -> Color>(
+    adaptive: &AdaptiveSampling,
+    mut sample: F,
+) -> (Color, usize) {
+    let mut sum = Color::new(0.0, 0.0, 0.0);
+    let (mut luminance_sum, mut luminance_sum2) = (0.0, 0.0);
+    let mut count = 0;
+    while count < adaptive.max_samples && !adaptive.converged(count, luminance_sum, luminance_sum2)
+    {
+        let batch = adaptive
+            .min_samples
+            .max(1)
+            .min(adaptive.max_samples - count);
+        for _ in 0..batch {
+            let color = sample(count);
+            let luminance = luminance(color);
+            sum += color;
+            luminance_sum += luminance;
+            luminance_sum2 += luminance * luminance;
+            count += 1;
+        }
+    }
+    (sum, count)
+}
+
+/// Like `render`, but takes the samples in batches of `batch_size` and calls `on_batch` after
+/// each with the average of the samples so far and their count per pixel, e.g. to save a
+/// preview. Every pixel keeps its RNG and running sum between batches, so the returned sums are
+/// exactly those of `render`. With adaptive sampling it falls back to `render` and calls
+/// `on_batch` once at the end.
+pub fn render_progressive<H: Hittable, F: FnMut(&[Color], usize)>(
+    camera: &Camera,
+    world: &H,
+    background: &Background,
+    settings: &RenderSettings,
+    batch_size: usize,
+    mut on_batch: F,
+) -> Vec<Color> {
+    if settings.adaptive_sampling.is_some() {
+        let sums = render(camera, world, background, settings);
+        let count = settings.samples_per_pixel;
+        let average: Vec<Color> = sums.iter().map(|sum| sum / count as Float).collect();
+        on_batch(&average, count);
+        return sums;
+    }
+    let pixel_count = settings.width * settings.height;
+    let batch_size = batch_size.max(1);
+    let grid_size = grid_size(settings);
+    let mut rngs: Vec<_> = (0..pixel_count)
+        .map(|index| pixel_rng(index, settings))
+        .collect();
+    let mut sums = vec![Color::new(0.0, 0.0, 0.0); pixel_count];
+    let progress = Progress::new(settings.samples_per_pixel.div_ceil(batch_size));
+    let mut count = 0;
+    while count < settings.samples_per_pixel {
+        let samples = count..(count + batch_size).min(settings.samples_per_pixel);
+        rngs.par_iter_mut()
+            .zip(sums.par_iter_mut())
+            .enumerate()
+            .for_each(|(index, (rng, sum))| {
+                for sample_index in samples.clone() {
+                    let ray = primary_ray(index, sample_index, grid_size, camera, settings, rng);
+                    let color = ray_color::<Color, _, _>(&ray, world, background, settings, rng);
+                    *sum += clamp_sample(color, settings);
+                }
+            });
+        count = samples.end;
+        let average: Vec<Color> = sums.iter().map(|sum| sum / count as Float).collect();
+        on_batch(&average, count);
+        progress.advance();
+    }
+    progress.finish();
+    sums
+}
+
+/// Like `render`, but traces paths breadth first: each sample of a batch of pixels is advanced
+/// one bounce at a time over all of them, so rays of similar depth run together. Gives the same
+/// image as `render`, which it falls back to for adaptive sampling and the first-hit modes.
+pub fn render_wavefront<H: Hittable>(
+    camera: &Camera,
+    world: &H,
+    background: &Background,
+    settings: &RenderSettings,
+) -> Vec<Color> {
+    /// Pixels whose paths are traced together
+    const BATCH_SIZE: usize = 4096;
+
+    match (&settings.adaptive_sampling, settings.mode) {
+        (None, RenderMode::Beauty) => {}
+        _ => return render(camera, world, background, settings),
+    }
+    let pixel_count = settings.width * settings.height;
+    let grid_size = grid_size(settings);
+    let progress = Progress::new(pixel_count);
+    let batches: Vec<Vec<Color>> =
+        (0..pixel_count.div_ceil(BATCH_SIZE))
+            .into_par_iter()
+            .map(|batch| {
+                let pixels = batch * BATCH_SIZE..((batch + 1) * BATCH_SIZE).min(pixel_count);
+                let mut rngs: Vec<_> = pixels
+                    .clone()
+                    .map(|index| pixel_rng(index, settings))
+                    .collect();
+                let mut sums = vec![Color::new(0.0, 0.0, 0.0); pixels.len()];
+                let mut paths = Vec::with_capacity(pixels.len());
+                for sample_index in 0..settings.samples_per_pixel {
+                    // every pixel draws from its own RNG in the same order as in `render`
+                    paths.extend(pixels.clone().zip(&mut rngs).enumerate().map(
+                        |(i, (index, rng))| {
+                            let ray =
+                                primary_ray(index, sample_index, grid_size, camera, settings, rng);
+                            (i, PathState::<Color>::new(ray, settings))
+                        },
+                    ));
+                    while !paths.is_empty() {
+                        paths.retain_mut(|(i, path)| {
+                            let alive = path.step(world, background, settings, &mut rngs[*i]);
+                            if !alive {
+                                sums[*i] += clamp_sample(path.accumulated, settings);
+                            }
+                            alive
+                        });
+                    }
+                }
+                for _ in pixels {
+                    progress.advance();
+                }
+                sums
+            })
+            .collect();
+    progress.finish();
+    batches.concat()
+}
+
+/// Cells per side of the jitter grid shared by all pixels, if any
+fn grid_size(settings: &RenderSettings) -> Option<usize> {
+    // sample counts vary per pixel with adaptive sampling, so no grid fits them all
+    match settings.adaptive_sampling {
+        Some(_) => None,
+        None => settings
+            .sample_strategy
+            .grid_size(settings.samples_per_pixel),
+    }
+}
+
+/// RNG of the pixel at `index`; it draws all of the pixel's samples in turn
+fn pixel_rng(index: usize, settings: &RenderSettings) -> SmallRng {
+    SmallRng::seed_from_u64(
+        (index as u64).wrapping_add(settings.seed.wrapping_mul(0x9e37_79b9_7f4a_7c15)),
+    )
+}
+
+/// Camera ray of the `sample_index`th sample of the pixel at `index`
+fn primary_ray<R: Rng>(
+    index: usize,
+    sample_index: usize,
+    grid_size: Option<usize>,
+    camera: &Camera,
+    settings: &RenderSettings,
+    rng: &mut R,
+) -> Ray {
+    let distribution = Uniform::from(0.0..1.0);
+    let mut dx = distribution.sample(rng);
+    let mut dy = distribution.sample(rng);
+    if let Some(n) = grid_size {
+        dx = ((sample_index % n) as Float + dx) / n as Float;
+        dy = ((sample_index / n) as Float + dy) / n as Float;
+    }
+    let (u, v) = pixel_uv(index, dx, dy, settings);
+    camera.ray(u, v, rng)
+}
+
+/// Viewport coordinates of the point at offset `(dx, dy)` in `[0, 1)` inside the pixel at
+/// `index`. Pixels tile the viewport exactly, with `v` growing upwards from the bottom row.
+fn pixel_uv(index: usize, dx: Float, dy: Float, settings: &RenderSettings) -> (Float, Float) {
+    let width = settings.width;
+    let height = settings.height;
+    let x = index % width;
+    let y = index / width;
+    let u = (x as Float + dx) / width as Float;
+    let v = ((height - 1 - y) as Float + dy) / height as Float;
+    (u, v)
+}
+
+fn clamp_sample(color: Color, settings: &RenderSettings) -> Color {
+    match settings.clamp_radiance {
+        Some(max) => color.map(|channel| channel.min(max)),
+        None => color,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use super::*;
+    use crate::{
+        bvh::BvhNode,
+        hittable::{BoxPrim, HittableList, Plane, Sphere, Triangle, XyRect, XzRect},
+        material::Material,
+        output::{color_to_rgb8, write_ppm_binary, write_ppm_header, write_rgb8, ToneMap},
+    };
+
+    fn black() -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    fn settings() -> RenderSettings {
+        RenderSettings {
+            width: 8,
+            height: 6,
+            samples_per_pixel: 4,
+            max_depth: 10,
+            fog: Fog {
+                density: 0.0,
+                color: black(),
+            },
+            sample_strategy: SampleStrategy::Uniform,
+            russian_roulette_depth: None,
+            clamp_radiance: None,
+            seed: 0,
+            adaptive_sampling: None,
+            lights: None,
+            light_sampling: LightSampling::Brdf,
+            mode: RenderMode::Beauty,
+        }
+    }
+
+    fn ray_toward_origin() -> Ray {
+        Ray::new(Point3::new(0.0, 0.0, 5.0), -Vector3::unit_z())
+    }
+
+    #[test]
+    fn emissive_surfaces_light_a_dark_scene() {
+        let background = Background::SolidColor(black());
+        let mut rng = SmallRng::seed_from_u64(0);
+        let emit = Color::new(2.0, 4.0, 6.0);
+        let light = Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Material::new_diffuse_light(emit),
+        );
+        let color: Color = ray_color(
+            &ray_toward_origin(),
+            &light,
+            &background,
+            &settings(),
+            &mut rng,
+        );
+        assert_eq!(color, emit);
+
+        // every bounce off the diffuse sphere ends on the light around it
+        let world = vec![
+            Sphere::new(
+                Point3::new(0.0, 0.0, 0.0),
+                1.0,
+                Material::new_lambertian(Color::new(0.5, 0.5, 0.5)),
+            ),
+            Sphere::new_inverted(
+                Point3::new(0.0, 0.0, 0.0),
+                10.0,
+                Material::new_diffuse_light(emit),
+            ),
+        ];
+        for _ in 0..16 {
+            let color: Color = ray_color(
+                &ray_toward_origin(),
+                &world,
+                &background,
+                &settings(),
+                &mut rng,
+            );
+            assert!((color - 0.5 * emit).magnitude() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn missed_rays_return_the_background() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let nothing: Vec<Sphere> = Vec::new();
+        let color: Color = ray_color(
+            &ray_toward_origin(),
+            &nothing,
+            &Background::SolidColor(black()),
+            &settings(),
+            &mut rng,
+        );
+        assert_eq!(color, black());
+    }
+
+    fn camera(settings: &RenderSettings) -> Camera {
+        Camera::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 0.0, -1.0),
+            Vector3::unit_y(),
+            90.0,
+            settings.width as Float / settings.height as Float,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        )
+    }
+
+    fn small_scene() -> Vec<Sphere> {
+        vec![
+            Sphere::new(
+                Point3::new(0.0, 0.0, -2.0),
+                1.0,
+                Material::new_lambertian(Color::new(0.7, 0.3, 0.2)),
+            ),
+            Sphere::new(
+                Point3::new(0.0, -101.0, -2.0),
+                100.0,
+                Material::new_metal(Color::new(0.8, 0.8, 0.8), 0.3),
+            ),
+        ]
+    }
+
+    fn sky() -> Background {
+        Background::Gradient {
+            top: Color::new(0.5, 0.7, 1.0),
+            bottom: Color::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    #[test]
+    fn renders_rows_from_the_top_and_reproducibly() {
+        let settings = settings();
+        let camera = camera(&settings);
+        let vertical = Background::Gradient {
+            top: Color::new(1.0, 1.0, 1.0),
+            bottom: black(),
+        };
+        let nothing: Vec<Sphere> = Vec::new();
+        let pixels = render(&camera, &nothing, &vertical, &settings);
+        assert_eq!(pixels.len(), settings.width * settings.height);
+        let last_row = settings.width * (settings.height - 1);
+        assert!(pixels[0].x > pixels[last_row].x);
+
+        let world = small_scene();
+        assert_eq!(
+            render(&camera, &world, &sky(), &settings),
+            render(&camera, &world, &sky(), &settings)
+        );
+    }
+
+    #[test]
+    fn thicker_colored_glass_is_darker() {
+        let glass = |thickness: Float| {
+            BoxPrim::new(
+                Point3::new(-5.0, -5.0, -thickness),
+                Point3::new(5.0, 5.0, 0.0),
+                Material::new_absorbing_dielectric(1.5, Color::new(0.1, 0.5, 0.5)),
+            )
+        };
+        let background = Background::SolidColor(Color::new(1.0, 1.0, 1.0));
+        let mean_through = |slab: &BoxPrim| {
+            let mut rng = SmallRng::seed_from_u64(0);
+            let sum: Color = (0..1000)
+                .map(|_| {
+                    ray_color::<Color, _, _>(
+                        &ray_toward_origin(),
+                        slab,
+                        &background,
+                        &settings(),
+                        &mut rng,
+                    )
+                })
+                .sum();
+            sum / 1000.0
+        };
+        let thin = mean_through(&glass(0.5));
+        let thick = mean_through(&glass(2.0));
+        assert!(
+            thick.y < thin.y && thick.z < thin.z,
+            "{:?} {:?}",
+            thin,
+            thick
+        );
+        assert!((thin.y - (-0.25 as Float).exp()).abs() < 0.1);
+    }
+
+    /// Variance over seeds of a single-pixel render of a white background 30% covered by a black
+    /// rectangle
+    fn edge_pixel_variance(sample_strategy: SampleStrategy) -> Float {
+        let camera = Camera::new_orthographic(
+            Point3::new(0.0, 0.0, 1.0),
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::unit_y(),
+            2.0,
+            1.0,
+        );
+        let black_rect = XyRect::new(
+            -5.0,
+            -0.4,
+            -5.0,
+            5.0,
+            0.0,
+            Material::new_lambertian(black()),
+        );
+        let white = Background::SolidColor(Color::new(1.0, 1.0, 1.0));
+        let values: Vec<Float> = (0..200)
+            .map(|seed| {
+                let settings = RenderSettings {
+                    width: 1,
+                    height: 1,
+                    samples_per_pixel: 16,
+                    sample_strategy,
+                    seed,
+                    ..settings()
+                };
+                render(&camera, &black_rect, &white, &settings)[0].x / 16.0
+            })
+            .collect();
+        let mean = values.iter().sum::<Float>() / values.len() as Float;
+        assert!((mean - 0.7).abs() < 0.05, "mean {}", mean);
+        values
+            .iter()
+            .map(|value| (value - mean).powi(2))
+            .sum::<Float>()
+            / values.len() as Float
+    }
+
+    #[test]
+    fn stratified_sampling_lowers_variance() {
+        let uniform = edge_pixel_variance(SampleStrategy::Uniform);
+        let stratified = edge_pixel_variance(SampleStrategy::Stratified);
+        assert!(stratified < uniform / 2.0, "{} vs {}", stratified, uniform);
+    }
+
+    /// Mean and standard error of many samples of a ray bouncing around a diffuse room lit by
+    /// a sphere in its middle
+    fn room_estimate(russian_roulette_depth: Option<usize>) -> (Float, Float) {
+        const SAMPLES: usize = 20_000;
+        let objects: Vec<Arc<dyn Hittable>> = vec![
+            Arc::new(Sphere::new(
+                Point3::new(0.0, 0.0, 0.0),
+                0.5,
+                Material::new_diffuse_light(Color::new(1.0, 1.0, 1.0)),
+            )),
+            Arc::new(Sphere::new_inverted(
+                Point3::new(0.0, 0.0, 0.0),
+                3.0,
+                Material::new_lambertian(Color::new(0.8, 0.8, 0.8)),
+            )),
+        ];
+        // a hierarchy rather than a list, whose hits fan out over threads
+        let world = BvhNode::new(objects, &mut SmallRng::seed_from_u64(0));
+        let settings = RenderSettings {
+            max_depth: 50,
+            russian_roulette_depth,
+            ..settings()
+        };
+        let ray = Ray::new(Point3::new(0.0, 0.0, 2.0), Vector3::new(0.0, 1.0, 0.2));
+        let mut rng = SmallRng::seed_from_u64(russian_roulette_depth.is_some() as u64);
+        estimate(SAMPLES, || {
+            ray_color::<Color, _, _>(
+                &ray,
+                &world,
+                &Background::SolidColor(black()),
+                &settings,
+                &mut rng,
+            )
+            .x
+        })
+    }
+
+    /// Mean of `count` samples and its standard error
+    fn estimate<F: FnMut() -> Float>(count: usize, mut sample: F) -> (Float, Float) {
+        let samples: Vec<Float> = (0..count).map(|_| sample()).collect();
+        let mean = samples.iter().sum::<Float>() / count as Float;
+        let variance = samples
+            .iter()
+            .map(|sample| (sample - mean).powi(2))
+            .sum::<Float>()
+            / count as Float;
+        (mean, (variance / count as Float).sqrt())
+    }
+
+    #[test]
+    fn russian_roulette_keeps_the_mean() {
+        let (exact, exact_error) = room_estimate(None);
+        let (roulette, roulette_error) = room_estimate(Some(2));
+        let tolerance = 4.0 * (exact_error.powi(2) + roulette_error.powi(2)).sqrt();
+        assert!(
+            (exact - roulette).abs() < tolerance,
+            "{} vs {} (tolerance {})",
+            exact,
+            roulette,
+            tolerance
+        );
+    }
+
+    /// `ray_color` as it was before radiance was accumulated forward: segments are pushed on a
+    /// stack and folded from the last one back to the camera.
+    fn stack_fold_color<H: Hittable, R: Rng>(
+        ray: &Ray,
+        world: &H,
+        background: &Background,
+        settings: &RenderSettings,
+        rng: &mut R,
+    ) -> Color {
+        let mut ray = ray.clone();
+        let mut depth = settings.max_depth;
+        let mut t_min = DEFAULT_EPSILON;
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut stack = Vec::new();
+        let radiance = loop {
+            if depth == 0 {
+                break black();
+            }
+            let record = match world.hit(&ray, t_min..Float::INFINITY) {
+                Some(record) => record,
+                None => break background.color(ray.direction),
+            };
+            let emitted = record.material.emitted();
+            let (scattered, attenuation) =
+                match record.material.scatter::<Color, _>(&ray, &record, rng) {
+                    Some(scattered) => scattered,
+                    None => break emitted,
+                };
+            ray = scattered;
+            t_min = record.t_min;
+            depth -= 1;
+            throughput = throughput.mul_element_wise(attenuation);
+            let survival = match settings.russian_roulette_depth {
+                Some(roulette_depth) if settings.max_depth - depth > roulette_depth => {
+                    throughput.x.max(throughput.y).max(throughput.z).min(1.0)
+                }
+                _ => 1.0,
+            };
+            if survival < 1.0 && rng.gen::<Float>() >= survival {
+                stack.push((emitted, attenuation));
+                break black();
+            }
+            throughput /= survival;
+            stack.push((emitted, attenuation / survival));
+        };
+        stack
+            .into_iter()
+            .rev()
+            .fold(radiance, |radiance, (emitted, attenuation)| {
+                emitted + attenuation.mul_element_wise(radiance)
+            })
+    }
+
+    #[test]
+    fn forward_accumulation_matches_the_stack_fold() {
+        let settings = RenderSettings {
+            width: 32,
+            height: 24,
+            russian_roulette_depth: Some(3),
+            ..settings()
+        };
+        let camera = camera(&settings);
+        let world = small_scene();
+        let mut largest_error: Float = 0.0;
+        for index in 0..settings.width * settings.height {
+            for sample_index in 0..4 {
+                let mut rng = pixel_rng(index, &settings);
+                let ray = primary_ray(index, sample_index, None, &camera, &settings, &mut rng);
+                let mut stack_rng = rng.clone();
+                let color: Color = ray_color(&ray, &world, &sky(), &settings, &mut rng);
+                let expected = stack_fold_color(&ray, &world, &sky(), &settings, &mut stack_rng);
+                for channel in 0..3 {
+                    let error = (color[channel] - expected[channel]).abs();
+                    largest_error =
+                        largest_error.max(error / expected[channel].max(Float::MIN_POSITIVE));
+                }
+            }
+        }
+        // only the order of the multiplications differs
+        assert!(largest_error <= 4.0 * Float::EPSILON, "{:e}", largest_error);
+    }
+
+    #[test]
+    fn clamping_caps_each_channel_of_a_bright_sample() {
+        let firefly = Color::new(250.0, 3.0, 0.5);
+        let clamped = RenderSettings {
+            clamp_radiance: Some(10.0),
+            ..settings()
+        };
+        assert_eq!(clamp_sample(firefly, &clamped), Color::new(10.0, 3.0, 0.5));
+        assert_eq!(clamp_sample(firefly, &settings()), firefly);
+    }
+
+    #[test]
+    fn seeds_give_independent_but_reproducible_noise() {
+        let seeded = |seed| RenderSettings { seed, ..settings() };
+        let world = small_scene();
+        let image = |seed| render(&camera(&seeded(seed)), &world, &sky(), &seeded(seed));
+        let first = image(1);
+        assert_eq!(first, image(1));
+        let second = image(2);
+        let differing = first.iter().zip(&second).filter(|(a, b)| a != b).count();
+        assert!(differing > first.len() / 2, "{} pixels differ", differing);
+    }
+
+    #[test]
+    fn flat_pixels_converge_long_before_high_contrast_ones() {
+        let adaptive = AdaptiveSampling {
+            min_samples: 16,
+            max_samples: 1024,
+            tolerance: 0.01,
+        };
+        let (sum, flat) = sample_adaptively(&adaptive, |_| Color::new(0.5, 0.5, 0.5));
+        assert_eq!(flat, 16);
+        assert_eq!(sum, Color::new(8.0, 8.0, 8.0));
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        let (_, contrast) = sample_adaptively(&adaptive, |_| {
+            if rng.gen::<Float>() < 0.1 {
+                Color::new(10.0, 10.0, 10.0)
+            } else {
+                black()
+            }
+        });
+        assert!(contrast > 8 * flat, "{} samples", contrast);
+    }
+
+    /// Estimate of the radiance along `ray` into a closed room lit by a small ceiling panel
+    fn lit_room_estimate(ray: &Ray, light_sampling: LightSampling) -> (Float, Float) {
+        let light = Arc::new(XzRect::new(
+            -0.3,
+            0.3,
+            -0.3,
+            0.3,
+            2.5,
+            Material::new_diffuse_light(Color::new(20.0, 20.0, 20.0)),
+        ));
+        let objects: Vec<Arc<dyn Hittable>> = vec![
+            light.clone(),
+            Arc::new(Sphere::new_inverted(
+                Point3::new(0.0, 0.0, 0.0),
+                3.0,
+                Material::new_lambertian(Color::new(0.5, 0.5, 0.5)),
+            )),
+        ];
+        let world = BvhNode::new(objects, &mut SmallRng::seed_from_u64(0));
+        let settings = RenderSettings {
+            max_depth: 5,
+            lights: Some(light),
+            light_sampling,
+            ..settings()
+        };
+        let mut rng = SmallRng::seed_from_u64(0);
+        estimate(10_000, || {
+            ray_color::<Color, _, _>(
+                ray,
+                &world,
+                &Background::SolidColor(black()),
+                &settings,
+                &mut rng,
+            )
+            .x
+        })
+    }
+
+    #[test]
+    fn next_event_estimation_converges_faster() {
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vector3::new(0.3, -1.0, 0.2));
+        let (brdf, brdf_error) = lit_room_estimate(&ray, LightSampling::Brdf);
+        let (next_event, next_event_error) = lit_room_estimate(&ray, LightSampling::NextEvent);
+        assert!(next_event_error < brdf_error / 3.0);
+        let tolerance = 4.0 * (brdf_error.powi(2) + next_event_error.powi(2)).sqrt();
+        assert!((brdf - next_event).abs() < tolerance);
+    }
+
+    /// Estimate from `samples` samples of the radiance along `ray` into a floor under a wide,
+    /// low panel light
+    fn panel_estimate(
+        ray: &Ray,
+        light_sampling: LightSampling,
+        samples: usize,
+        seed: u64,
+    ) -> (Float, Float) {
+        let light = Arc::new(XzRect::new(
+            -3.0,
+            3.0,
+            -3.0,
+            3.0,
+            0.3,
+            Material::new_diffuse_light(Color::new(4.0, 4.0, 4.0)),
+        ));
+        let objects: Vec<Arc<dyn Hittable>> = vec![
+            light.clone(),
+            Arc::new(Plane::new(
+                Point3::new(0.0, 0.0, 0.0),
+                Vector3::unit_y(),
+                Material::new_lambertian(Color::new(0.5, 0.5, 0.5)),
+            )),
+        ];
+        let world = BvhNode::new(objects, &mut SmallRng::seed_from_u64(0));
+        let settings = RenderSettings {
+            max_depth: 3,
+            lights: Some(light),
+            light_sampling,
+            ..settings()
+        };
+        let mut rng = SmallRng::seed_from_u64(seed);
+        estimate(samples, || {
+            ray_color::<Color, _, _>(
+                ray,
+                &world,
+                &Background::SolidColor(black()),
+                &settings,
+                &mut rng,
+            )
+            .x
+        })
+    }
+
+    #[test]
+    fn multiple_importance_sampling_is_closest_to_the_reference() {
+        // right under the panel, where light samples vary wildly, and far from it, where BRDF
+        // samples rarely find it
+        let rays = [
+            Ray::new(Point3::new(0.0, 0.15, 2.0), Vector3::new(0.0, -0.15, -2.0)),
+            Ray::new(Point3::new(0.0, 1.0, -8.0), Vector3::new(0.0, -1.0, -1.0)),
+        ];
+        let references: Vec<Float> = rays
+            .iter()
+            .map(|ray| panel_estimate(ray, LightSampling::MultipleImportance, 100_000, 0).0)
+            .collect();
+        // worst relative distance to the reference, counting the standard error as well
+        let error = |light_sampling| {
+            rays.iter()
+                .zip(&references)
+                .map(|(ray, reference)| {
+                    let (mean, error) = panel_estimate(ray, light_sampling, 2_000, 1);
+                    ((mean - reference).powi(2) + error.powi(2)).sqrt() / reference
+                })
+                .fold(0.0, Float::max)
+        };
+        let brdf = error(LightSampling::Brdf);
+        let next_event = error(LightSampling::NextEvent);
+        let multiple_importance = error(LightSampling::MultipleImportance);
+        assert!(multiple_importance < brdf && multiple_importance < next_event);
+    }
+
+    #[test]
+    fn normal_mode_shows_the_sphere_normal_facing_the_camera() {
+        let settings = RenderSettings {
+            mode: RenderMode::Normal,
+            ..settings()
+        };
+        let sphere = Sphere::new(
+            Point3::new(0.0, 0.0, -3.0),
+            1.0,
+            Material::new_lambertian(Color::new(0.5, 0.5, 0.5)),
+        );
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut trace =
+            |ray: Ray| ray_color::<Color, _, _>(&ray, &sphere, &sky(), &settings, &mut rng);
+        let center: Color = trace(Ray::new(Point3::new(0.0, 0.0, 0.0), -Vector3::unit_z()));
+        assert!((center - Color::new(0.5, 0.5, 1.0)).magnitude() < 1e-6);
+        let top = trace(Ray::new(Point3::new(0.0, 0.999, 0.0), -Vector3::unit_z()));
+        assert!(top.y > 0.95);
+        assert_eq!(
+            trace(Ray::new(Point3::new(0.0, 0.0, 0.0), Vector3::unit_z())),
+            black()
+        );
+    }
+
+    #[test]
+    fn interrupted_tiled_render_resumes_to_the_same_image() {
+        let settings = RenderSettings {
+            width: 10,
+            height: 7,
+            ..settings()
+        };
+        let camera = camera(&settings);
+        let world = small_scene();
+        let path = std::env::temp_dir().join(format!("ray-tracing-{}.tiles", std::process::id()));
+        let expected = render(&camera, &world, &sky(), &settings);
+        let tiled = render_tiled(&camera, &world, &sky(), &settings, 4, &path).unwrap();
+        assert_eq!(tiled, expected);
+
+        // cut the last of the 6 tiles short, as if the process had been killed while saving it
+        let length = std::fs::metadata(&path).unwrap().len();
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(length - 5)
+            .unwrap();
+        let resumed = render_tiled(&camera, &world, &sky(), &settings, 4, &path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(resumed.unwrap(), expected);
+    }
+
+    #[test]
+    fn wavefront_render_matches_render() {
+        let settings = RenderSettings {
+            russian_roulette_depth: Some(2),
+            sample_strategy: SampleStrategy::Stratified,
+            ..settings()
+        };
+        let camera = camera(&settings);
+        let world = small_scene();
+        assert_eq!(
+            render_wavefront(&camera, &world, &sky(), &settings),
+            render(&camera, &world, &sky(), &settings)
+        );
+    }
+
+    const REFERENCE_PATH: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/small_scene_f64.png"
+    );
+
+    fn small_scene_bytes() -> Vec<u8> {
+        let settings = RenderSettings {
+            width: 24,
+            height: 16,
+            samples_per_pixel: 64,
+            ..settings()
+        };
+        render(&camera(&settings), &small_scene(), &sky(), &settings)
+            .into_iter()
+            .flat_map(|color| color_to_rgb8(color, settings.samples_per_pixel, 2.0, ToneMap::None))
+            .collect()
+    }
+
+    /// Compares against an image rendered with `f64`, which a build with the `f32` feature
+    /// matches up to rounding
+    #[test]
+    fn renders_agree_with_the_f64_reference() {
+        let reference = image::open(REFERENCE_PATH).unwrap().to_rgb8().into_raw();
+        let bytes = small_scene_bytes();
+        assert_eq!(bytes.len(), reference.len());
+        let differences: Vec<Float> = bytes
+            .iter()
+            .zip(&reference)
+            .map(|(&a, &b)| (a as Float - b as Float).abs())
+            .collect();
+        let mean_difference = differences.iter().sum::<Float>() / differences.len() as Float;
+        let mean =
+            |bytes: &[u8]| bytes.iter().map(|&b| b as Float).sum::<Float>() / bytes.len() as Float;
+        assert!(mean_difference < 0.5, "mean difference {}", mean_difference);
+        assert!((mean(&bytes) - mean(&reference)).abs() < 0.2);
+    }
+
+    #[test]
+    fn progressive_batches_accumulate_to_the_full_render() {
+        let batched = RenderSettings {
+            samples_per_pixel: 8,
+            ..settings()
+        };
+        let camera = camera(&batched);
+        let world = small_scene();
+        let mut batches = Vec::new();
+        let sums = render_progressive(&camera, &world, &sky(), &batched, 3, |average, count| {
+            batches.push((average.to_vec(), count))
+        });
+        assert_eq!(sums, render(&camera, &world, &sky(), &batched));
+        let counts: Vec<usize> = batches.iter().map(|(_, count)| *count).collect();
+        assert_eq!(counts, [3, 6, 8]);
+        let (last, _) = batches.last().unwrap();
+        for (average, sum) in last.iter().zip(&sums) {
+            assert_eq!(*average, sum / 8.0);
+        }
+
+        let adaptive = || RenderSettings {
+            adaptive_sampling: Some(AdaptiveSampling {
+                min_samples: 4,
+                max_samples: 16,
+                tolerance: 0.05,
+            }),
+            ..settings()
+        };
+        let mut counts = Vec::new();
+        let sums = render_progressive(&camera, &world, &sky(), &adaptive(), 3, |_, count| {
+            counts.push(count)
+        });
+        assert_eq!(sums, render(&camera, &world, &sky(), &adaptive()));
+        assert_eq!(counts, [adaptive().samples_per_pixel]);
+    }
+
+    #[test]
+    fn clean_glass_shows_white_instead_of_the_sky_after_internal_reflections() {
+        let background = Background::SolidColor(Color::new(0.0, 0.0, 1.0));
+        let white = Color::new(1.0, 1.0, 1.0);
+        let glass = |material| Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, material);
+        let (tinted, clean) = (
+            glass(Material::new_dielectric(1.5)),
+            glass(Material::new_clean_dielectric(1.5)),
+        );
+        let ray = Ray::new(Point3::new(0.8, 0.0, 5.0), -Vector3::unit_z());
+        let mut whites = 0;
+        for seed in 0..256 {
+            let trace = |world: &Sphere| -> Color {
+                let mut rng = SmallRng::seed_from_u64(seed);
+                ray_color(&ray, world, &background, &settings(), &mut rng)
+            };
+            let (tinted, clean) = (trace(&tinted), trace(&clean));
+            // plain glass only ever sees the blue sky
+            assert_eq!(tinted.x, 0.0);
+            if clean == white {
+                whites += 1;
+            } else {
+                assert_eq!(clean, tinted);
+            }
+        }
+        assert!(whites > 0);
+    }
+
+    #[test]
+    fn leaving_clean_glass_brings_the_sky_back() {
+        let blue = Color::new(0.0, 0.0, 1.0);
+        let background = Background::SolidColor(blue);
+        let wall = Sphere::new(
+            Point3::new(0.0, 0.0, 0.0),
+            1.0,
+            Material::new_lambertian(Color::new(1.0, 1.0, 1.0)),
+        );
+        let settings = settings();
+        let mut rng = SmallRng::seed_from_u64(0);
+        // as if the ray had just escaped clean glass after reflecting inside it
+        let mut path = PathState::<Color>::new(ray_toward_origin(), &settings);
+        path.hide_background = true;
+        while path.step(&wall, &background, &settings, &mut rng) {}
+        assert_eq!(path.accumulated, blue);
+    }
+
+    #[test]
+    fn pixels_tile_the_viewport_from_edge_to_edge() {
+        let settings = settings();
+        let (width, height) = (settings.width, settings.height);
+        let last = width * height - 1;
+        let below_one = 1.0 - Float::EPSILON;
+
+        // the first pixel is the top-left one and covers [0, 1/w) horizontally
+        assert_eq!(pixel_uv(0, 0.0, 0.0, &settings).0, 0.0);
+        assert!(pixel_uv(0, below_one, 0.0, &settings).0 < 1.0 / width as Float);
+        assert_eq!(
+            pixel_uv(0, 0.0, 0.0, &settings).1,
+            (height - 1) as Float / height as Float
+        );
+        assert_eq!(pixel_uv(0, 0.0, 1.0, &settings).1, 1.0);
+
+        // the last pixel is the bottom-right one and reaches 1 horizontally
+        assert_eq!(pixel_uv(last, 1.0, 0.0, &settings).0, 1.0);
+        assert_eq!(
+            pixel_uv(last, 0.0, 0.0, &settings).0,
+            (width - 1) as Float / width as Float
+        );
+        assert_eq!(pixel_uv(last, 0.0, 0.0, &settings).1, 0.0);
+
+        // neighbours share their edges, so there are no gaps or overlaps
+        for index in 0..last {
+            if (index + 1) % width != 0 {
+                assert_eq!(
+                    pixel_uv(index, 1.0, 0.0, &settings).0,
+                    pixel_uv(index + 1, 0.0, 0.0, &settings).0
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fog_grows_with_distance_and_vanishes_at_zero_density() {
+        let emit = Color::new(4.0, 2.0, 1.0);
+        let fog_color = Color::new(0.5, 0.5, 0.5);
+        let light_at = |distance: Float| {
+            Sphere::new(
+                Point3::new(0.0, 0.0, 5.0 - distance - 1.0),
+                1.0,
+                Material::new_diffuse_light(emit),
+            )
+        };
+        let trace = |density: Float, world: &Sphere| -> Color {
+            let settings = RenderSettings {
+                fog: Fog {
+                    density,
+                    color: fog_color,
+                },
+                ..settings()
+            };
+            let mut rng = SmallRng::seed_from_u64(0);
+            ray_color(&ray_toward_origin(), world, &sky(), &settings, &mut rng)
+        };
+
+        let (near, far) = (trace(0.1, &light_at(1.0)), trace(0.1, &light_at(10.0)));
+        let expected = |distance: Float| {
+            let transmittance = (-0.1 * distance).exp();
+            emit * transmittance + fog_color * (1.0 - transmittance)
+        };
+        assert!((near - expected(1.0)).magnitude() < 1e-5);
+        assert!((far - expected(10.0)).magnitude() < 1e-5);
+        assert!((far - fog_color).magnitude() < (near - fog_color).magnitude());
+
+        assert_eq!(trace(0.0, &light_at(1.0)), emit);
+        assert_eq!(trace(0.0, &light_at(10.0)), emit);
+        let nothing: Vec<Sphere> = Vec::new();
+        let clear = RenderSettings {
+            fog: Fog {
+                density: 0.0,
+                color: fog_color,
+            },
+            ..settings()
+        };
+        let missed: Color = ray_color(
+            &ray_toward_origin(),
+            &nothing,
+            &sky(),
+            &clear,
+            &mut SmallRng::seed_from_u64(0),
+        );
+        assert_eq!(missed, sky().color(ray_toward_origin().direction));
+    }
+
+    #[test]
+    fn streamed_rows_give_the_same_bytes_as_the_buffered_render() {
+        let streamed_settings = RenderSettings {
+            width: 4,
+            height: 4,
+            ..settings()
+        };
+        let camera = camera(&streamed_settings);
+        let world = small_scene();
+        let encode = |pixels: &[Color], writer: &mut Vec<u8>| {
+            write_rgb8(
+                writer,
+                pixels,
+                streamed_settings.samples_per_pixel,
+                2.0,
+                ToneMap::None,
+            )
+        };
+
+        let mut buffered = Vec::new();
+        write_ppm_binary(
+            &mut buffered,
+            &render(&camera, &world, &sky(), &streamed_settings),
+            4,
+            4,
+            streamed_settings.samples_per_pixel,
+            2.0,
+            ToneMap::None,
+        )
+        .unwrap();
+
+        let mut streamed = Vec::new();
+        let mut rows = 0;
+        write_ppm_header(&mut streamed, 4, 4).unwrap();
+        render_rows(&camera, &world, &sky(), &streamed_settings, |row| {
+            assert_eq!(row.len(), 4);
+            rows += 1;
+            encode(row, &mut streamed)
+        })
+        .unwrap();
+        assert_eq!(rows, 4);
+        assert_eq!(streamed, buffered);
+    }
+
+    #[test]
+    fn scattered_rays_start_at_the_near_clip_of_their_hit() {
+        let settings = settings();
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mirror = Material::new_metal(Color::new(0.9, 0.9, 0.9), 0.0);
+        let triangle = Triangle::new(
+            Point3::new(-1.0, -1.0, 0.0),
+            Point3::new(1.0, -1.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            mirror,
+        )
+        .with_epsilon(0.05);
+        let mut path = PathState::<Color>::new(ray_toward_origin(), &settings);
+        assert_eq!(path.t_min, DEFAULT_EPSILON);
+        assert!(path.step(&triangle, &sky(), &settings, &mut rng));
+        assert_eq!(path.t_min, 0.05);
+        assert_eq!(path.ray.direction, Vector3::unit_z());
+    }
+
+    #[test]
+    fn transparent_spheres_are_invisible() {
+        let glass = Sphere::new(Point3::new(0.0, 0.0, 0.0), 1.0, Material::new_transparent());
+        let mut rng = SmallRng::seed_from_u64(0);
+        let ray = Ray::new(Point3::new(0.3, 0.2, 5.0), Vector3::new(-0.1, 0.05, -1.0));
+        assert!(glass.hit(&ray, DEFAULT_EPSILON..Float::INFINITY).is_some());
+
+        // both walls pass the ray on unchanged
+        let mut path = PathState::<Color>::new(ray.clone(), &settings());
+        for _ in 0..2 {
+            assert!(path.step(&glass, &sky(), &settings(), &mut rng));
+            assert_eq!(path.ray.direction, ray.direction);
+            assert_eq!(path.throughput, Color::new(1.0, 1.0, 1.0));
+        }
+        let color: Color = ray_color(&ray, &glass, &sky(), &settings(), &mut rng);
+        assert_eq!(color, sky().color(ray.direction));
+    }
+
+    #[test]
+    fn encoded_output_is_byte_identical_across_runs() {
+        let settings = settings();
+        let camera = camera(&settings);
+        let world = BvhNode::new(
+            small_scene()
+                .into_iter()
+                .map(|sphere| Arc::new(sphere) as Arc<dyn Hittable>)
+                .collect(),
+            &mut SmallRng::seed_from_u64(0),
+        );
+        let encode = || {
+            let mut bytes = Vec::new();
+            write_ppm_binary(
+                &mut bytes,
+                &render(&camera, &world, &sky(), &settings),
+                settings.width,
+                settings.height,
+                settings.samples_per_pixel,
+                2.0,
+                ToneMap::None,
+            )
+            .unwrap();
+            bytes
+        };
+        let first = encode();
+        assert_eq!(first.len(), "P6\n8 6\n255\n".len() + 3 * 8 * 6);
+        assert_eq!(encode(), first);
+    }
+
+    #[test]
+    fn lights_in_a_list_are_each_sampled() {
+        let panel = |x: Float, emit: Float| {
+            Arc::new(XzRect::new(
+                x - 0.3,
+                x + 0.3,
+                -0.3,
+                0.3,
+                2.5,
+                Material::new_diffuse_light(Color::new(emit, emit, emit)),
+            ))
+        };
+        let (left, right) = (panel(-1.0, 10.0), panel(1.0, 30.0));
+        let mut lights = HittableList::new();
+        lights.add(left.clone());
+        lights.add(right.clone());
+        let lights: Arc<dyn Hittable> = Arc::new(lights);
+        let objects: Vec<Arc<dyn Hittable>> = vec![
+            left,
+            right,
+            Arc::new(Sphere::new_inverted(
+                Point3::new(0.0, 0.0, 0.0),
+                3.0,
+                Material::new_lambertian(Color::new(0.5, 0.5, 0.5)),
+            )),
+        ];
+        let world = BvhNode::new(objects, &mut SmallRng::seed_from_u64(0));
+        let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vector3::new(0.2, -1.0, 0.1));
+        let room_estimate = |light_sampling| {
+            let sampled = RenderSettings {
+                max_depth: 5,
+                lights: Some(lights.clone()),
+                light_sampling,
+                ..settings()
+            };
+            let mut rng = SmallRng::seed_from_u64(0);
+            estimate(10_000, || {
+                ray_color::<Color, _, _>(
+                    &ray,
+                    &world,
+                    &Background::SolidColor(black()),
+                    &sampled,
+                    &mut rng,
+                )
+                .x
+            })
+        };
+
+        let (brdf, brdf_error) = room_estimate(LightSampling::Brdf);
+        for light_sampling in [LightSampling::NextEvent, LightSampling::MultipleImportance] {
+            let (sampled, sampled_error) = room_estimate(light_sampling);
+            assert!(sampled_error < brdf_error);
+            let tolerance = 4.0 * (brdf_error.powi(2) + sampled_error.powi(2)).sqrt();
+            assert!(
+                (brdf - sampled).abs() < tolerance,
+                "{} vs {} (tolerance {})",
+                brdf,
+                sampled,
+                tolerance
+            );
+        }
+    }
+}
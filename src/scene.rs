@@ -0,0 +1,411 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use cgmath::{ElementWise, InnerSpace, Point3, Vector3};
+use image::ImageResult;
+use rand::{distributions::Uniform, prelude::Distribution, rngs::SmallRng, SeedableRng};
+
+use crate::{
+    background::Background,
+    bvh::BvhNode,
+    camera::Camera,
+    description::SceneDescription,
+    hittable::{
+        BoxPrim, Hittable, HittableList, Plane, RotateY, Sphere, Translate, XyRect, XzRect, YzRect,
+    },
+    material::Material,
+    output::{save_png, ToneMap},
+    render::{
+        render, render_progressive, render_rows, render_tiled, render_wavefront, Fog,
+        LightSampling, RenderMode, RenderSettings, SampleStrategy,
+    },
+    Color, Float,
+};
+
+/// Everything needed to render an image: what to look at, from where, and how.
+pub struct Scene {
+    pub world: Arc<dyn Hittable>,
+    pub camera: Camera,
+    pub background: Background,
+    pub settings: RenderSettings,
+}
+
+impl Scene {
+    /// Renders the scene, returning the per-pixel sample sums.
+    pub fn render(&self) -> Vec<Color> {
+        render(&self.camera, &self.world, &self.background, &self.settings)
+    }
+
+    /// Renders the scene row by row, see `render_rows`.
+    pub fn render_rows<F: FnMut(&[Color]) -> io::Result<()>>(&self, on_row: F) -> io::Result<()> {
+        render_rows(
+            &self.camera,
+            &self.world,
+            &self.background,
+            &self.settings,
+            on_row,
+        )
+    }
+
+    /// Renders the scene in batches of samples, see `render_progressive`.
+    pub fn render_progressive<F: FnMut(&[Color], usize)>(
+        &self,
+        batch_size: usize,
+        on_batch: F,
+    ) -> Vec<Color> {
+        render_progressive(
+            &self.camera,
+            &self.world,
+            &self.background,
+            &self.settings,
+            batch_size,
+            on_batch,
+        )
+    }
+
+    /// Renders the scene breadth first, see `render_wavefront`.
+    pub fn render_wavefront(&self) -> Vec<Color> {
+        render_wavefront(&self.camera, &self.world, &self.background, &self.settings)
+    }
+
+    /// Renders the scene tile by tile, resuming from and saving to the checkpoint at
+    /// `checkpoint_path`.
+    pub fn render_tiled<P: AsRef<Path>>(
+        &self,
+        tile_size: usize,
+        checkpoint_path: P,
+    ) -> io::Result<Vec<Color>> {
+        render_tiled(
+            &self.camera,
+            &self.world,
+            &self.background,
+            &self.settings,
+            tile_size,
+            checkpoint_path,
+        )
+    }
+
+    /// The cover of "Ray Tracing in One Weekend": small random spheres around three big ones.
+    pub fn random_spheres() -> Self {
+        const ASPECT_RATIO: Float = 3.0 / 2.0;
+        const IMAGE_WIDTH: usize = 1200;
+        const IMAGE_HEIGHT: usize = (IMAGE_WIDTH as Float / ASPECT_RATIO) as usize;
+        const SAMPLES_PER_PIXEL: usize = 500;
+        const MAX_DEPTH: usize = 50;
+        const RUSSIAN_ROULETTE_DEPTH: usize = 5;
+        const FOG_DENSITY: Float = 0.0;
+
+        let ground_material = Material::new_lambertian(Color::new(0.5, 0.5, 0.5));
+        let mut hittables = HittableList::new();
+        hittables.add(Plane::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            ground_material,
+        ));
+
+        let mut rng = SmallRng::seed_from_u64(
+            0b0101010101010101_0101010101010101_0101010101010101_0101010101010101,
+        );
+        let distribution = Uniform::from(0.0..1.0);
+        let dielectric = Material::new_dielectric(1.5);
+        for a in -11..11 {
+            for b in -11..11 {
+                let material_probability = distribution.sample(&mut rng);
+                let center = Point3::new(
+                    a as Float + 0.9 * distribution.sample(&mut rng),
+                    0.2,
+                    b as Float + 0.9 * distribution.sample(&mut rng),
+                );
+
+                if (center - Point3::new(4.0, 0.2, 0.0))
+                    .dot(center - Point3::new(4.0, 0.2, 0.0))
+                    .sqrt()
+                    > 0.9
+                {
+                    let material = if material_probability < 0.8 {
+                        let albedo = Color::new(
+                            distribution.sample(&mut rng),
+                            distribution.sample(&mut rng),
+                            distribution.sample(&mut rng),
+                        )
+                        .mul_element_wise(Color::new(
+                            distribution.sample(&mut rng),
+                            distribution.sample(&mut rng),
+                            distribution.sample(&mut rng),
+                        ));
+                        Material::new_lambertian(albedo)
+                    } else if material_probability < 0.95 {
+                        let distribution = Uniform::from(0.5..1.0);
+                        let albedo = Color::new(
+                            distribution.sample(&mut rng),
+                            distribution.sample(&mut rng),
+                            distribution.sample(&mut rng),
+                        );
+                        let distribution = Uniform::from(0.0..0.5);
+                        let fuzz = distribution.sample(&mut rng);
+                        Material::new_metal(albedo, fuzz)
+                    } else {
+                        dielectric.clone()
+                    };
+                    hittables.add(Sphere::new(center, 0.2, material));
+                }
+            }
+        }
+        hittables.add(Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, dielectric));
+        hittables.add(Sphere::new(
+            Point3::new(-4.0, 1.0, 0.0),
+            1.0,
+            Material::new_lambertian(Color::new(0.4, 0.2, 0.1)),
+        ));
+        hittables.add(Sphere::new(
+            Point3::new(4.0, 1.0, 0.0),
+            1.0,
+            Material::new_metal(Color::new(0.7, 0.6, 0.5), 0.0),
+        ));
+        let world = Arc::new(BvhNode::new(hittables.into_objects(), &mut rng));
+
+        let camera_position = Point3::new(13.0, 3.0, 2.0);
+        let camera_look_at = Point3::new(0.0, 0.0, 0.0);
+        let up = Vector3::new(0.0, 1.0, 0.0);
+        let aperture = 0.1;
+        let camera = Camera::new(
+            camera_position,
+            camera_look_at,
+            up,
+            20.0,
+            ASPECT_RATIO,
+            aperture,
+            10.0,
+            0.0,
+            0.0,
+        );
+
+        let background = Background::Gradient {
+            top: Color::new(0.5, 0.7, 1.0),
+            bottom: Color::new(1.0, 1.0, 1.0),
+        };
+        let settings = RenderSettings {
+            width: IMAGE_WIDTH,
+            height: IMAGE_HEIGHT,
+            samples_per_pixel: SAMPLES_PER_PIXEL,
+            max_depth: MAX_DEPTH,
+            fog: Fog {
+                density: FOG_DENSITY,
+                color: Color::new(0.8, 0.8, 0.8),
+            },
+            sample_strategy: SampleStrategy::Stratified,
+            russian_roulette_depth: Some(RUSSIAN_ROULETTE_DEPTH),
+            clamp_radiance: None,
+            seed: 0,
+            adaptive_sampling: None,
+            lights: None,
+            light_sampling: LightSampling::Brdf,
+            mode: RenderMode::Beauty,
+        };
+
+        Self {
+            world,
+            camera,
+            background,
+            settings,
+        }
+    }
+
+    /// The Cornell box of "Ray Tracing: The Next Week" with its ceiling light sampled directly.
+    pub fn cornell_box() -> Self {
+        let red = Material::new_lambertian(Color::new(0.65, 0.05, 0.05));
+        let white = Material::new_lambertian(Color::new(0.73, 0.73, 0.73));
+        let green = Material::new_lambertian(Color::new(0.12, 0.45, 0.15));
+        let light = Arc::new(XzRect::new(
+            213.0,
+            343.0,
+            227.0,
+            332.0,
+            554.0,
+            Material::new_diffuse_light(Color::new(15.0, 15.0, 15.0)),
+        ));
+
+        let mut hittables = HittableList::new();
+        hittables.add(YzRect::new(0.0, 555.0, 0.0, 555.0, 555.0, green));
+        hittables.add(YzRect::new(0.0, 555.0, 0.0, 555.0, 0.0, red));
+        hittables.add(light.clone());
+        hittables.add(XzRect::new(0.0, 555.0, 0.0, 555.0, 0.0, white.clone()));
+        hittables.add(XzRect::new(0.0, 555.0, 0.0, 555.0, 555.0, white.clone()));
+        hittables.add(XyRect::new(0.0, 555.0, 0.0, 555.0, 555.0, white.clone()));
+        let tall_box = BoxPrim::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(165.0, 330.0, 165.0),
+            white.clone(),
+        );
+        hittables.add(Translate::new(
+            Arc::new(RotateY::new(Arc::new(tall_box), 15.0)),
+            Vector3::new(265.0, 0.0, 295.0),
+        ));
+        let short_box = BoxPrim::new(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(165.0, 165.0, 165.0),
+            white,
+        );
+        hittables.add(Translate::new(
+            Arc::new(RotateY::new(Arc::new(short_box), -18.0)),
+            Vector3::new(130.0, 0.0, 65.0),
+        ));
+        let mut rng = SmallRng::seed_from_u64(0);
+        let world = Arc::new(BvhNode::new(hittables.into_objects(), &mut rng));
+
+        let camera = Camera::new(
+            Point3::new(278.0, 278.0, -800.0),
+            Point3::new(278.0, 278.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            40.0,
+            1.0,
+            0.0,
+            10.0,
+            0.0,
+            0.0,
+        );
+        let settings = RenderSettings {
+            width: 600,
+            height: 600,
+            samples_per_pixel: 200,
+            max_depth: 50,
+            fog: Fog {
+                density: 0.0,
+                color: Color::new(0.0, 0.0, 0.0),
+            },
+            sample_strategy: SampleStrategy::Stratified,
+            russian_roulette_depth: Some(5),
+            clamp_radiance: None,
+            seed: 0,
+            adaptive_sampling: None,
+            lights: Some(light),
+            light_sampling: LightSampling::MultipleImportance,
+            mode: RenderMode::Beauty,
+        };
+
+        Self {
+            world,
+            camera,
+            background: Background::SolidColor(Color::new(0.0, 0.0, 0.0)),
+            settings,
+        }
+    }
+}
+
+/// Renders `frames` frames of the scene `scene_fn` returns for times spread evenly over
+/// `0..=1`, and saves them as `frame_0000.png`, `frame_0001.png`, ... in `out_dir`, which is
+/// created if needed. Each frame adds its index to the scene's seed, so reruns are identical
+/// while noise still changes from frame to frame. Returns the paths of the frames.
+pub fn render_animation<F: Fn(Float) -> Scene, P: AsRef<Path>>(
+    scene_fn: F,
+    frames: usize,
+    out_dir: P,
+    gamma: Float,
+    tone_map: ToneMap,
+) -> ImageResult<Vec<PathBuf>> {
+    let out_dir = out_dir.as_ref();
+    fs::create_dir_all(out_dir)?;
+    (0..frames)
+        .map(|frame| {
+            let time = if frames > 1 {
+                frame as Float / (frames - 1) as Float
+            } else {
+                0.0
+            };
+            let mut scene = scene_fn(time);
+            scene.settings.seed = scene.settings.seed.wrapping_add(frame as u64);
+            let pixels = scene.render();
+            let path = out_dir.join(format!("frame_{:04}.png", frame));
+            let settings = &scene.settings;
+            save_png(
+                &path,
+                &pixels,
+                settings.width,
+                settings.height,
+                settings.samples_per_pixel,
+                gamma,
+                tone_map,
+            )?;
+            Ok(path)
+        })
+        .collect()
+}
+
+/// Reads a scene from a JSON `SceneDescription`. Unknown object or material types, and unknown
+/// fields, are reported as errors.
+pub fn load_json<P: AsRef<Path>>(path: P) -> serde_json::Result<Scene> {
+    let file = File::open(path).map_err(serde_json::Error::io)?;
+    let description: SceneDescription = serde_json::from_reader(BufReader::new(file))?;
+    Ok(description.into_scene())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moving_sphere_scene(time: Float) -> Scene {
+        let sphere = Sphere::new(
+            Point3::new(-1.0 + 2.0 * time, 0.0, -3.0),
+            0.5,
+            Material::new_lambertian(Color::new(0.8, 0.2, 0.2)),
+        );
+        let settings = RenderSettings {
+            width: 16,
+            height: 8,
+            samples_per_pixel: 4,
+            max_depth: 4,
+            fog: Fog {
+                density: 0.0,
+                color: Color::new(0.0, 0.0, 0.0),
+            },
+            sample_strategy: SampleStrategy::Uniform,
+            russian_roulette_depth: None,
+            clamp_radiance: None,
+            seed: 0,
+            adaptive_sampling: None,
+            lights: None,
+            light_sampling: LightSampling::Brdf,
+            mode: RenderMode::Beauty,
+        };
+        Scene {
+            world: Arc::new(vec![sphere]),
+            camera: Camera::new(
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(0.0, 0.0, -1.0),
+                Vector3::unit_y(),
+                60.0,
+                2.0,
+                0.0,
+                1.0,
+                0.0,
+                0.0,
+            ),
+            background: Background::SolidColor(Color::new(1.0, 1.0, 1.0)),
+            settings,
+        }
+    }
+
+    #[test]
+    fn animation_writes_one_distinct_png_per_frame() {
+        let out_dir =
+            std::env::temp_dir().join(format!("ray-tracing-{}-frames", std::process::id()));
+        let paths = render_animation(moving_sphere_scene, 3, &out_dir, 2.0, ToneMap::None).unwrap();
+        let frames: Vec<Vec<u8>> = paths
+            .iter()
+            .map(|path| image::open(path).unwrap().to_rgb8().into_raw())
+            .collect();
+        fs::remove_dir_all(&out_dir).unwrap();
+        let names: Vec<_> = paths.iter().map(|path| path.file_name().unwrap()).collect();
+        assert_eq!(
+            names,
+            ["frame_0000.png", "frame_0001.png", "frame_0002.png"]
+        );
+        assert_ne!(frames[0], frames[1]);
+        assert_ne!(frames[1], frames[2]);
+        assert_ne!(frames[0], frames[2]);
+    }
+}
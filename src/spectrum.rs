@@ -0,0 +1,172 @@
+use std::{
+    iter::Sum,
+    ops::{Add, Div, Mul},
+};
+
+use cgmath::ElementWise;
+
+use crate::{Color, Float};
+
+/// Radiance carried along a path.
+///
+/// Materials and the background are still described in RGB, so a spectrum only has to
+/// know how to convert from and to RGB and how to be attenuated by another spectrum.
+pub trait Spectrum:
+    Copy
+    + Send
+    + Sync
+    + Add<Output = Self>
+    + Mul<Float, Output = Self>
+    + Div<Float, Output = Self>
+    + Sum
+{
+    fn from_rgb(rgb: Color) -> Self;
+
+    fn to_rgb(self) -> Color;
+
+    fn mul_spectrum(self, other: Self) -> Self;
+}
+
+impl Spectrum for Color {
+    fn from_rgb(rgb: Color) -> Self {
+        rgb
+    }
+
+    fn to_rgb(self) -> Color {
+        self
+    }
+
+    fn mul_spectrum(self, other: Self) -> Self {
+        self.mul_element_wise(other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{InnerSpace, Point3, Vector3};
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    use crate::{
+        background::Background,
+        hittable::{Hittable, Sphere, DEFAULT_EPSILON},
+        material::Material,
+        render::{ray_color, Fog, LightSampling, RenderMode, RenderSettings, SampleStrategy},
+        Ray,
+    };
+
+    use super::*;
+
+    /// Recursive RGB path tracer as it was before radiance became generic over `Spectrum`
+    fn color_path<H: Hittable, R: Rng>(
+        ray: &Ray,
+        world: &H,
+        background: &Background,
+        t_min: Float,
+        depth: usize,
+        rng: &mut R,
+    ) -> Color {
+        if depth == 0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let record = match world.hit(ray, t_min..Float::INFINITY) {
+            Some(record) => record,
+            None => return background.color(ray.direction),
+        };
+        let emitted = record.material.emitted();
+        match record.material.scatter::<Color, _>(ray, &record, rng) {
+            Some((scattered, attenuation)) => {
+                emitted
+                    + attenuation.mul_element_wise(color_path(
+                        &scattered,
+                        world,
+                        background,
+                        record.t_min,
+                        depth - 1,
+                        rng,
+                    ))
+            }
+            None => emitted,
+        }
+    }
+
+    #[test]
+    fn rgb_spectrum_matches_the_color_path() {
+        let world = vec![
+            Sphere::new(
+                Point3::new(0.0, 0.0, -2.0),
+                1.0,
+                Material::new_lambertian(Color::new(0.7, 0.3, 0.2)),
+            ),
+            Sphere::new(
+                Point3::new(0.0, -101.0, -2.0),
+                100.0,
+                Material::new_metal(Color::new(0.8, 0.8, 0.8), 0.3),
+            ),
+            Sphere::new(
+                Point3::new(1.5, 1.0, -2.0),
+                0.5,
+                Material::new_diffuse_light(Color::new(4.0, 4.0, 4.0)),
+            ),
+        ];
+        let background = Background::Gradient {
+            top: Color::new(0.5, 0.7, 1.0),
+            bottom: Color::new(1.0, 1.0, 1.0),
+        };
+        let settings = RenderSettings {
+            width: 8,
+            height: 6,
+            samples_per_pixel: 1,
+            max_depth: 10,
+            fog: Fog {
+                density: 0.0,
+                color: Color::new(0.0, 0.0, 0.0),
+            },
+            sample_strategy: SampleStrategy::Uniform,
+            russian_roulette_depth: None,
+            clamp_radiance: None,
+            seed: 0,
+            adaptive_sampling: None,
+            lights: None,
+            light_sampling: LightSampling::Brdf,
+            mode: RenderMode::Beauty,
+        };
+        for x in -4..4 {
+            for y in -3..3 {
+                let direction = Vector3::new(x as Float + 0.5, y as Float + 0.5, -4.0).normalize();
+                let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), direction);
+                let seed = (x * 8 + y) as u64;
+                let expected = color_path(
+                    &ray,
+                    &world,
+                    &background,
+                    DEFAULT_EPSILON,
+                    settings.max_depth,
+                    &mut SmallRng::seed_from_u64(seed),
+                );
+                let color: Color = ray_color(
+                    &ray,
+                    &world,
+                    &background,
+                    &settings,
+                    &mut SmallRng::seed_from_u64(seed),
+                );
+                assert!(
+                    (color.to_rgb() - expected).magnitude() < 1e-4,
+                    "{:?} != {:?}",
+                    color,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rgb_spectrum_round_trips_and_multiplies_per_channel() {
+        let color = Color::new(0.25, 0.5, 2.0);
+        assert_eq!(Color::from_rgb(color).to_rgb(), color);
+        assert_eq!(
+            color.mul_spectrum(Color::new(2.0, 0.5, 0.25)),
+            Color::new(0.5, 0.25, 0.5)
+        );
+    }
+}
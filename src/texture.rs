@@ -0,0 +1,244 @@
+use std::{fs::File, io::BufReader, path::Path, sync::Arc};
+
+use cgmath::Point3;
+use image::{codecs::hdr::HdrDecoder, ImageResult};
+use rand::Rng;
+
+use crate::{perlin::Perlin, Color, Float};
+
+pub trait Texture: Send + Sync {
+    fn value(&self, u: Float, v: Float, p: Point3<Float>) -> Color;
+
+    /// The color everywhere if the texture is uniform, so materials using it can be serialized
+    fn solid_color(&self) -> Option<Color> {
+        None
+    }
+}
+
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> Self {
+        Self { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: Float, _v: Float, _p: Point3<Float>) -> Color {
+        self.color
+    }
+
+    fn solid_color(&self) -> Option<Color> {
+        Some(self.color)
+    }
+}
+
+/// 3D checker pattern alternating where `sin(scale x) sin(scale y) sin(scale z)` changes sign,
+/// or a floor grid of `1 / scale` wide cells on the xz plane
+pub struct CheckerTexture {
+    odd: Arc<dyn Texture>,
+    even: Arc<dyn Texture>,
+    scale: Float,
+    floor_grid: bool,
+}
+
+impl CheckerTexture {
+    pub fn new(odd: Arc<dyn Texture>, even: Arc<dyn Texture>, scale: Float) -> Self {
+        Self {
+            odd,
+            even,
+            scale,
+            floor_grid: false,
+        }
+    }
+
+    /// Creates a world-aligned grid whose cells are odd where `floor(scale x) + floor(scale z)`
+    /// is odd, for crisp calibration floors.
+    pub fn new_floor_grid(odd: Arc<dyn Texture>, even: Arc<dyn Texture>, scale: Float) -> Self {
+        Self {
+            odd,
+            even,
+            scale,
+            floor_grid: true,
+        }
+    }
+
+    pub fn from_colors(odd: Color, even: Color, scale: Float) -> Self {
+        Self::new(
+            Arc::new(SolidColor::new(odd)),
+            Arc::new(SolidColor::new(even)),
+            scale,
+        )
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: Float, v: Float, p: Point3<Float>) -> Color {
+        let is_odd = if self.floor_grid {
+            let cell = (self.scale * p.x).floor() as i64 + (self.scale * p.z).floor() as i64;
+            cell.rem_euclid(2) == 1
+        } else {
+            (self.scale * p.x).sin() * (self.scale * p.y).sin() * (self.scale * p.z).sin() < 0.0
+        };
+        if is_odd {
+            self.odd.value(u, v, p)
+        } else {
+            self.even.value(u, v, p)
+        }
+    }
+}
+
+/// Perlin noise, optionally with turbulence for marble-like veins
+pub struct NoiseTexture {
+    noise: Perlin,
+    scale: Float,
+    turbulence_octaves: Option<usize>,
+}
+
+impl NoiseTexture {
+    pub fn new<R: Rng>(scale: Float, rng: &mut R) -> Self {
+        Self {
+            noise: Perlin::new(rng),
+            scale,
+            turbulence_octaves: None,
+        }
+    }
+
+    /// Switches to a marble pattern: a sine along z phase-shifted by turbulence over `octaves`.
+    pub fn with_turbulence(mut self, octaves: usize) -> Self {
+        self.turbulence_octaves = Some(octaves);
+        self
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: Float, _v: Float, p: Point3<Float>) -> Color {
+        let value = match self.turbulence_octaves {
+            Some(octaves) => {
+                0.5 * (1.0 + (self.scale * p.z + 10.0 * self.noise.turbulence(p, octaves)).sin())
+            }
+            None => 0.5 * (1.0 + self.noise.noise(self.scale * p)),
+        };
+        value * Color::new(1.0, 1.0, 1.0)
+    }
+}
+
+/// Bitmap lookup, with `v` running bottom to top
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    /// Row-major from the top
+    pixels: Vec<Color>,
+}
+
+impl ImageTexture {
+    /// Loads an 8-bit image scaled to `0..1`, or a Radiance `.hdr` file with its radiance as is.
+    pub fn new<P: AsRef<Path>>(path: P) -> ImageResult<Self> {
+        let path = path.as_ref();
+        let is_hdr = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("hdr"));
+        if is_hdr {
+            let decoder = HdrDecoder::new(BufReader::new(File::open(path)?))?;
+            let metadata = decoder.metadata();
+            let pixels = decoder
+                .read_image_hdr()?
+                .into_iter()
+                .map(|pixel| Color::new(pixel[0] as Float, pixel[1] as Float, pixel[2] as Float))
+                .collect();
+            Ok(Self {
+                width: metadata.width as usize,
+                height: metadata.height as usize,
+                pixels,
+            })
+        } else {
+            let image = image::open(path)?.into_rgb8();
+            let pixels = image
+                .pixels()
+                .map(|pixel| {
+                    Color::new(pixel[0] as Float, pixel[1] as Float, pixel[2] as Float) / 255.0
+                })
+                .collect();
+            Ok(Self {
+                width: image.width() as usize,
+                height: image.height() as usize,
+                pixels,
+            })
+        }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: Float, v: Float, _p: Point3<Float>) -> Color {
+        if self.width == 0 || self.height == 0 {
+            return Color::new(0.0, 1.0, 1.0);
+        }
+
+        let u = u.clamp(0.0, 1.0);
+        let v = 1.0 - v.clamp(0.0, 1.0);
+        let i = ((u * self.width as Float) as usize).min(self.width - 1);
+        let j = ((v * self.height as Float) as usize).min(self.height - 1);
+        self.pixels[j * self.width + i]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn odd() -> Color {
+        Color::new(1.0, 0.0, 0.0)
+    }
+
+    fn even() -> Color {
+        Color::new(0.0, 0.0, 1.0)
+    }
+
+    #[test]
+    fn checker_picks_the_odd_color_where_the_sines_are_negative() {
+        let checker = CheckerTexture::from_colors(odd(), even(), 1.0);
+        assert_eq!(checker.value(0.0, 0.0, Point3::new(-1.0, 1.0, 1.0)), odd());
+        assert_eq!(checker.value(0.0, 0.0, Point3::new(1.0, 1.0, 1.0)), even());
+        assert_eq!(
+            checker.value(0.0, 0.0, Point3::new(-1.0, -1.0, 1.0)),
+            even()
+        );
+    }
+
+    #[test]
+    fn image_corners_sample_their_pixels() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/corners.png");
+        let image = ImageTexture::new(path).unwrap();
+        let at = |u, v| image.value(u, v, Point3::new(0.0, 0.0, 0.0));
+        assert_eq!(at(0.0, 1.0), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(at(1.0, 1.0), Color::new(0.0, 1.0, 0.0));
+        assert_eq!(at(0.0, 0.0), Color::new(0.0, 0.0, 1.0));
+        assert_eq!(at(1.0, 0.0), Color::new(1.0, 1.0, 1.0));
+        // out-of-range coordinates clamp to the edges
+        assert_eq!(at(-3.0, 7.0), Color::new(1.0, 0.0, 0.0));
+        assert_eq!(at(2.0, -1.0), Color::new(1.0, 1.0, 1.0));
+
+        assert!(ImageTexture::new("tests/fixtures/missing.png").is_err());
+    }
+
+    #[test]
+    fn floor_grid_cells_alternate_every_inverse_scale() {
+        let scale = 4.0;
+        let grid = CheckerTexture::new_floor_grid(
+            Arc::new(SolidColor::new(odd())),
+            Arc::new(SolidColor::new(even())),
+            scale,
+        );
+        let at = |x: Float, z: Float| grid.value(0.0, 0.0, Point3::new(x, 7.0, z));
+        let cell = 1.0 / scale;
+        assert_eq!(at(0.1 * cell, 0.1 * cell), even());
+        assert_eq!(at(1.1 * cell, 0.1 * cell), odd());
+        assert_eq!(at(0.1 * cell, 1.1 * cell), odd());
+        assert_eq!(at(1.1 * cell, 1.1 * cell), even());
+        assert_eq!(at(-0.1 * cell, 0.1 * cell), odd());
+        // the color holds across the whole cell
+        assert_eq!(at(0.05 * cell, 0.1 * cell), at(0.95 * cell, 0.9 * cell));
+    }
+}
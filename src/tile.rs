@@ -0,0 +1,208 @@
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    ops::Range,
+    path::Path,
+    sync::Mutex,
+};
+
+use crate::{Color, Float};
+
+/// Square tiles covering an image row by row from the top left; the last column and row are
+/// cut to fit.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Tiles {
+    pub width: usize,
+    pub height: usize,
+    pub size: usize,
+}
+
+impl Tiles {
+    pub fn new(width: usize, height: usize, size: usize) -> Self {
+        Self {
+            width,
+            height,
+            size: size.max(1),
+        }
+    }
+
+    fn columns(&self) -> usize {
+        self.width.div_ceil(self.size)
+    }
+
+    pub fn count(&self) -> usize {
+        self.columns() * self.height.div_ceil(self.size)
+    }
+
+    /// Columns and rows of pixels covered by `tile`
+    pub fn bounds(&self, tile: usize) -> (Range<usize>, Range<usize>) {
+        let x = tile % self.columns() * self.size;
+        let y = tile / self.columns() * self.size;
+        (
+            x..(x + self.size).min(self.width),
+            y..(y + self.size).min(self.height),
+        )
+    }
+
+    /// Indices into the image of the pixels of `tile`, row by row
+    pub fn pixels(&self, tile: usize) -> impl Iterator<Item = usize> {
+        let (columns, rows) = self.bounds(tile);
+        let width = self.width;
+        rows.flat_map(move |y| columns.clone().map(move |x| y * width + x))
+    }
+
+    fn pixel_count(&self, tile: usize) -> usize {
+        let (columns, rows) = self.bounds(tile);
+        columns.len() * rows.len()
+    }
+}
+
+const MAGIC: &[u8; 8] = b"RTTILES1";
+const HEADER_SIZE: usize = 32;
+const PIXEL_SIZE: usize = 3 * 8;
+
+/// Append-only file of finished tiles, so an interrupted render can pick up where it stopped.
+///
+/// All numbers are little endian. The header is the magic `RTTILES1` followed by the image
+/// width, height, tile size and samples per pixel as `u32` and the seed as `u64`. Each record
+/// is then a `u32` tile index followed by the pixel sums of the tile as `f64` RGB triples, row
+/// by row. A record cut short by an interruption is dropped on the next open.
+pub struct Checkpoint {
+    file: Mutex<File>,
+}
+
+impl Checkpoint {
+    /// Opens or creates the checkpoint at `path` and returns it with the tiles already in it.
+    ///
+    /// Fails with `InvalidData` when the file was written for other settings, since its tiles
+    /// would not fit; remove it to start over, as well as after changing the scene itself.
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        tiles: Tiles,
+        samples_per_pixel: usize,
+        seed: u64,
+    ) -> io::Result<(Self, HashMap<usize, Vec<Color>>)> {
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        header.extend_from_slice(MAGIC);
+        for value in &[tiles.width, tiles.height, tiles.size, samples_per_pixel] {
+            header.extend_from_slice(&(*value as u32).to_le_bytes());
+        }
+        header.extend_from_slice(&seed.to_le_bytes());
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        if data.is_empty() {
+            file.write_all(&header)?;
+            return Ok((
+                Self {
+                    file: Mutex::new(file),
+                },
+                HashMap::new(),
+            ));
+        }
+        if data.len() < HEADER_SIZE || data[..HEADER_SIZE] != header[..] {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "checkpoint was written for another image or settings",
+            ));
+        }
+
+        let mut finished = HashMap::new();
+        let mut offset = HEADER_SIZE;
+        while let Some(index) = data.get(offset..offset + 4) {
+            let tile = u32::from_le_bytes([index[0], index[1], index[2], index[3]]) as usize;
+            if tile >= tiles.count() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "checkpoint holds a tile outside the image",
+                ));
+            }
+            let end = offset + 4 + tiles.pixel_count(tile) * PIXEL_SIZE;
+            let pixels = match data.get(offset + 4..end) {
+                Some(pixels) => pixels,
+                None => break,
+            };
+            let pixels = pixels
+                .chunks_exact(PIXEL_SIZE)
+                .map(|pixel| {
+                    let channel = |i: usize| {
+                        let mut bytes = [0; 8];
+                        bytes.copy_from_slice(&pixel[i * 8..i * 8 + 8]);
+                        f64::from_le_bytes(bytes) as Float
+                    };
+                    Color::new(channel(0), channel(1), channel(2))
+                })
+                .collect();
+            finished.insert(tile, pixels);
+            offset = end;
+        }
+        // drop a partial record so new ones line up
+        file.set_len(offset as u64)?;
+        file.seek(SeekFrom::End(0))?;
+        Ok((
+            Self {
+                file: Mutex::new(file),
+            },
+            finished,
+        ))
+    }
+
+    /// Appends a finished tile and waits until it is on disk.
+    // pixels are stored as `f64` even when `Float` is `f32`
+    #[allow(clippy::unnecessary_cast)]
+    pub fn save(&self, tile: usize, pixels: &[Color]) -> io::Result<()> {
+        let mut record = Vec::with_capacity(4 + pixels.len() * PIXEL_SIZE);
+        record.extend_from_slice(&(tile as u32).to_le_bytes());
+        for pixel in pixels {
+            for channel in &[pixel.x, pixel.y, pixel.z] {
+                record.extend_from_slice(&(*channel as f64).to_le_bytes());
+            }
+        }
+        let mut file = self.file.lock().unwrap();
+        file.write_all(&record)?;
+        file.sync_data()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_cover_every_pixel_once() {
+        let tiles = Tiles::new(10, 7, 4);
+        assert_eq!(tiles.count(), 6);
+        let mut pixels: Vec<usize> = (0..tiles.count())
+            .flat_map(|tile| tiles.pixels(tile))
+            .collect();
+        pixels.sort_unstable();
+        assert_eq!(pixels, (0..70).collect::<Vec<_>>());
+        assert_eq!(tiles.bounds(5), (8..10, 4..7));
+    }
+
+    #[test]
+    fn reopening_keeps_saved_tiles_and_rejects_other_settings() {
+        let path =
+            std::env::temp_dir().join(format!("ray-tracing-tile-{}.tiles", std::process::id()));
+        let tiles = Tiles::new(3, 2, 2);
+        let pixels = vec![Color::new(0.25, 0.5, 1.0); 2];
+        {
+            let (checkpoint, finished) = Checkpoint::open(&path, tiles, 4, 7).unwrap();
+            assert!(finished.is_empty());
+            checkpoint.save(1, &pixels).unwrap();
+        }
+        let (_, finished) = Checkpoint::open(&path, tiles, 4, 7).unwrap();
+        let other_seed = Checkpoint::open(&path, tiles, 4, 8).map(|_| ());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(finished.len(), 1);
+        assert_eq!(finished[&1], pixels);
+        assert_eq!(other_seed.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,22 @@
+use cgmath::{InnerSpace, Point3, Vector3};
+use ray_tracing::{
+    hittable::{Hittable, Sphere},
+    material::Material,
+    Color, Float, Ray,
+};
+
+#[test]
+fn lambertian_sphere_is_hit_through_the_public_api() {
+    let material = Material::new_lambertian(Color::new(0.5, 0.5, 0.5));
+    let sphere = Sphere::new(Point3::new(0.0, 0.0, -2.0), 0.5, material);
+    let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), -Vector3::unit_z());
+
+    let record = sphere.hit(&ray, 0.001..Float::INFINITY).unwrap();
+    assert!((record.t - 1.5).abs() < 1e-6);
+    assert!((record.normal - Vector3::unit_z()).magnitude() < 1e-6);
+    assert!(record.front_face);
+    assert_eq!(record.material.albedo(&record), Color::new(0.5, 0.5, 0.5));
+
+    let away = Ray::new(Point3::new(0.0, 0.0, 0.0), Vector3::unit_z());
+    assert!(sphere.hit(&away, 0.001..Float::INFINITY).is_none());
+}